@@ -3,7 +3,7 @@ use rustree::{BoundingBox, Point, RTree};
 fn main() -> std::io::Result<()> {
     let mut rtree = RTree::new();
     let mut try_add = |x, y| {
-        let pt = Point { x, y };
+        let pt = Point::new(x, y);
         rtree.insert_entry(pt, BoundingBox { min: pt, max: pt });
         println!("inserted: {rtree:?}");
         println!("now bb: {:?}", rtree.bounding_box());
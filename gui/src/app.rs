@@ -1,11 +1,10 @@
 use crate::data::{ConvexHull, ConvexHulls};
-use ::rustree::{Point, RTree, RTreeNode, WalkCallbackPayload};
+use ::rustree::{Point, RTree, RTreeNode, RTreeObject, WalkCallbackPayload};
 use eframe::{
     egui::{self, Context, Ui},
     emath::Align2,
-    epaint::{pos2, vec2, Color32, FontId, PathShape, Pos2, Rect},
+    epaint::{pos2, Color32, FontId, PathShape, Pos2, Rect},
 };
-use rustree::BoundingBox;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum Mode {
@@ -23,6 +22,12 @@ pub struct RustreeApp {
     scale: f32,
 }
 
+impl Default for RustreeApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RustreeApp {
     pub fn new() -> Self {
         Self {
@@ -43,55 +48,40 @@ impl RustreeApp {
                 if response.clicked_by(egui::PointerButton::Primary) {
                     if let Some(pos) = response.interact_pointer_pos() {
                         let screen_pos = (pos - self.offset.to_vec2()) / self.scale;
-                        let pt = Point {
-                            x: screen_pos.x as f64,
-                            y: screen_pos.y as f64,
-                        };
+                        let pt = Point::new(screen_pos.x as f64, screen_pos.y as f64);
                         self.adding_polygon.push(pt);
                     }
                 } else if response.clicked_by(egui::PointerButton::Secondary) {
                     if let Some(pos) = response.interact_pointer_pos() {
                         let screen_pos = (pos - self.offset.to_vec2()) / self.scale;
-                        let pt = Point {
-                            x: screen_pos.x as f64,
-                            y: screen_pos.y as f64,
-                        };
+                        let pt = Point::new(screen_pos.x as f64, screen_pos.y as f64);
                         self.adding_polygon.push(pt);
                         if 3 <= self.adding_polygon.len() {
-                            let c_hull = ConvexHull {
-                                id: 0,
-                                apexes: std::mem::take(&mut self.adding_polygon),
-                            };
-                            if let Some(bb) = c_hull.bounding_box() {
-                                self.rtree.insert_entry(c_hull, bb);
+                            let apexes = std::mem::take(&mut self.adding_polygon);
+                            if let Some(c_hull) = ConvexHull::new(apexes) {
+                                self.rtree.insert(c_hull);
                             }
                         }
                     }
                 }
             }
-            Mode::AddPoint => {
-                if response.clicked() {
-                    if let Some(pos) = response.interact_pointer_pos() {
-                        let screen_pos = (pos - self.offset.to_vec2()) / self.scale;
-                        let pt = Point {
-                            x: screen_pos.x as f64,
-                            y: screen_pos.y as f64,
-                        };
-                        use std::f64::consts::PI;
-                        let theta0 = rand::random::<f64>() * PI * 2.;
-                        let r = rand::random::<f64>() * 3. + 3.;
-                        let apexes = (0..5)
-                            .map(|i| {
-                                let theta = i as f64 * PI * 2. / 5. + theta0;
-                                let x = r * theta.cos();
-                                let y = r * theta.sin();
-                                Point::new(x, y) + pt
-                            })
-                            .collect();
-                        let c_hull = ConvexHull { id: 0, apexes };
-                        if let Some(bb) = c_hull.bounding_box() {
-                            self.rtree.insert_entry(c_hull, bb);
-                        }
+            Mode::AddPoint if response.clicked() => {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let screen_pos = (pos - self.offset.to_vec2()) / self.scale;
+                    let pt = Point::new(screen_pos.x as f64, screen_pos.y as f64);
+                    use std::f64::consts::PI;
+                    let theta0 = rand::random::<f64>() * PI * 2.;
+                    let r = rand::random::<f64>() * 3. + 3.;
+                    let apexes = (0..5)
+                        .map(|i| {
+                            let theta = i as f64 * PI * 2. / 5. + theta0;
+                            let x = r * theta.cos();
+                            let y = r * theta.sin();
+                            Point::new(x, y) + pt
+                        })
+                        .collect();
+                    if let Some(c_hull) = ConvexHull::new(apexes) {
+                        self.rtree.insert(c_hull);
                     }
                 }
             }
@@ -113,8 +103,10 @@ impl RustreeApp {
             let bb = entry.bounding_box();
             match entry.node() {
                 RTreeNode::Node(_) => {
-                    let pos = pos2(bb.min.x as f32 * self.scale, bb.min.y as f32 * self.scale)
-                        + self.offset.to_vec2();
+                    let pos = pos2(
+                        bb.min.x() as f32 * self.scale,
+                        bb.min.y() as f32 * self.scale,
+                    ) + self.offset.to_vec2();
                     // painter.circle(pos, 3., Color32::GRAY, (1., Color32::from_rgb(0, 127, 0)));
                     painter.text(
                         pos,
@@ -127,10 +119,10 @@ impl RustreeApp {
                 RTreeNode::Leaf(c_hull) => {
                     painter.add(PathShape::convex_polygon(
                         c_hull
-                            .apexes
+                            .apexes()
                             .iter()
                             .map(|pt| {
-                                pos2(pt.x as f32 * self.scale, pt.y as f32 * self.scale)
+                                pos2(pt.x() as f32 * self.scale, pt.y() as f32 * self.scale)
                                     + self.offset.to_vec2()
                             })
                             .collect(),
@@ -142,10 +134,14 @@ impl RustreeApp {
 
             painter.rect_stroke(
                 Rect {
-                    min: pos2(bb.min.x as f32 * self.scale, bb.min.y as f32 * self.scale)
-                        + self.offset.to_vec2(),
-                    max: pos2(bb.max.x as f32 * self.scale, bb.max.y as f32 * self.scale)
-                        + self.offset.to_vec2(),
+                    min: pos2(
+                        bb.min.x() as f32 * self.scale,
+                        bb.min.y() as f32 * self.scale,
+                    ) + self.offset.to_vec2(),
+                    max: pos2(
+                        bb.max.x() as f32 * self.scale,
+                        bb.max.y() as f32 * self.scale,
+                    ) + self.offset.to_vec2(),
                 },
                 0.,
                 (
@@ -157,15 +153,12 @@ impl RustreeApp {
 
         if self.mode == Mode::AddPolygon {
             let transform_point = |pt: &Point| {
-                pos2(pt.x as f32 * self.scale, pt.y as f32 * self.scale) + self.offset.to_vec2()
+                pos2(pt.x() as f32 * self.scale, pt.y() as f32 * self.scale) + self.offset.to_vec2()
             };
 
             if 2 <= self.adding_polygon.len() {
                 painter.add(PathShape::line(
-                    self.adding_polygon
-                        .iter()
-                        .map(|pt| transform_point(pt))
-                        .collect(),
+                    self.adding_polygon.iter().map(transform_point).collect(),
                     (1., Color32::from_rgb(127, 41, 41)),
                 ));
             }
@@ -184,16 +177,13 @@ impl RustreeApp {
             if let Some(pos) = response.hover_pos() {
                 let point_pos = (pos - self.offset.to_vec2()) / self.scale;
                 let pt = Point::new(point_pos.x as f64, point_pos.y as f64);
-                for c_hull in self.rtree.find_multi(&BoundingBox::from_center_size(
-                    pt,
-                    Point::new(self.query_radius, self.query_radius),
-                )) {
+                for c_hull in self.rtree.locate_within_distance(&pt, self.query_radius) {
                     painter.add(PathShape::convex_polygon(
                         c_hull
-                            .apexes
+                            .apexes()
                             .iter()
                             .map(|pt| {
-                                pos2(pt.x as f32 * self.scale, pt.y as f32 * self.scale)
+                                pos2(pt.x() as f32 * self.scale, pt.y() as f32 * self.scale)
                                     + self.offset.to_vec2()
                             })
                             .collect(),
@@ -211,15 +201,11 @@ impl RustreeApp {
                     // );
                 }
 
-                // Query rectangle
+                // Query circle
                 let screen_radius = (self.query_radius * self.scale as f64) as f32;
-                let screen_offset = vec2(screen_radius, screen_radius);
-                painter.rect(
-                    Rect {
-                        min: pos - screen_offset,
-                        max: pos + screen_offset,
-                    },
-                    0.,
+                painter.circle(
+                    pos,
+                    screen_radius,
                     Color32::from_rgba_unmultiplied(0, 255, 0, 63),
                     (2., Color32::from_rgb(0, 255, 0)),
                 );
@@ -228,16 +214,21 @@ impl RustreeApp {
     }
 
     fn reset() -> RTree<ConvexHull> {
-        let json = std::fs::read("convex_hulls.json").unwrap_or_else(|_| include_str!("../../convex_hulls.json").as_bytes().to_vec());
+        let json = std::fs::read("convex_hulls.json")
+            .unwrap_or_else(|_| include_str!("../../convex_hulls.json").as_bytes().to_vec());
         let deserialized: ConvexHulls =
             serde_json::from_str(std::str::from_utf8(&json).unwrap()).unwrap();
-        let mut rtree = RTree::new();
-        for c_hull in deserialized.convex_hulls {
-            if let Some(bbox) = c_hull.bounding_box() {
-                rtree.insert_entry(c_hull, bbox);
-            }
-        }
-        rtree
+        RTree::bulk_load(
+            deserialized
+                .convex_hulls
+                .into_iter()
+                .filter_map(|c_hull| ConvexHull::new(c_hull.apexes))
+                .map(|c_hull| {
+                    let bbox = c_hull.envelope();
+                    (c_hull, bbox)
+                })
+                .collect(),
+        )
     }
 
     fn show_side_panel(&mut self, ui: &mut Ui) {
@@ -1,32 +1,113 @@
-use rustree::{BoundingBox, Point};
+use rustree::{BoundingBox, ExactGeometry, Point, PointDistance, RTreeObject};
 use serde::Deserialize;
 
+/// A convex hull as it appears in the data file, which may have no apexes.
 #[derive(Deserialize, Debug)]
-pub struct ConvexHull {
-    #[serde(rename = "ID")]
-    pub id: usize,
+pub struct ConvexHullData {
     pub apexes: Vec<Point>,
 }
 
+/// A convex hull with at least one apex, so that it always has an envelope.
+#[derive(Debug)]
+pub struct ConvexHull {
+    apexes: Vec<Point>,
+}
+
+impl RTreeObject for ConvexHull {
+    fn envelope(&self) -> BoundingBox {
+        self.apexes
+            .iter()
+            .map(|apex| apex.envelope())
+            .reduce(|a, b| a.get_union(&b))
+            .expect("ConvexHull::new rejects empty apexes")
+    }
+}
+
 impl ConvexHull {
-    pub fn bounding_box(&self) -> Option<BoundingBox> {
-        let mut min: Option<Point> = None;
-        let mut max: Option<Point> = None;
-        for apex in &self.apexes {
-            min = min
-                .map(|min| Point::new(min.x.min(apex.x), min.y.min(apex.y)))
-                .or(Some(*apex));
-            max = max
-                .map(|max| Point::new(max.x.max(apex.x), max.y.max(apex.y)))
-                .or(Some(*apex));
+    /// Returns `None` if there are no apexes.
+    pub fn new(apexes: Vec<Point>) -> Option<Self> {
+        (!apexes.is_empty()).then_some(Self { apexes })
+    }
+
+    pub fn apexes(&self) -> &[Point] {
+        &self.apexes
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.apexes
+            .iter()
+            .zip(self.apexes.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
+    }
+}
+
+impl ExactGeometry for ConvexHull {
+    /// Separating axis test with the axes of the box and the normals of the edges.
+    fn intersects_box(&self, bounding_box: &BoundingBox) -> bool {
+        if !self.envelope().intersects(bounding_box) {
+            return false;
+        }
+        let (min, max) = (bounding_box.min, bounding_box.max);
+        let corners = [
+            min,
+            Point::new(max.x(), min.y()),
+            max,
+            Point::new(min.x(), max.y()),
+        ];
+        self.edges().all(|(a, b)| {
+            let normal = Point::new(a.y() - b.y(), b.x() - a.x());
+            let project = |pts: &mut dyn Iterator<Item = &Point>| {
+                pts.map(|p| p.x() * normal.x() + p.y() * normal.y())
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                        (lo.min(v), hi.max(v))
+                    })
+            };
+            let (hull_min, hull_max) = project(&mut self.apexes.iter());
+            let (box_min, box_max) = project(&mut corners.iter());
+            hull_min <= box_max && box_min <= hull_max
+        })
+    }
+
+    /// Inside if the point is on the same side of all the edges, whichever the winding is.
+    fn contains_point(&self, point: &Point) -> bool {
+        let cross = |(a, b): (Point, Point)| {
+            let (ab, ap) = (b - a, *point - a);
+            ab.x() * ap.y() - ab.y() * ap.x()
+        };
+        3 <= self.apexes.len()
+            && (self.edges().all(|edge| 0. <= cross(edge))
+                || self.edges().all(|edge| cross(edge) <= 0.))
+    }
+
+    fn distance_to_point(&self, point: &Point) -> f64 {
+        self.distance2_to_point(point).sqrt()
+    }
+}
+
+impl PointDistance for ConvexHull {
+    /// The squared distance to the closest edge, or 0 inside.
+    fn distance2_to_point(&self, point: &Point) -> f64 {
+        if self.contains_point(point) {
+            return 0.;
         }
-        min.zip(max)
-            .map(|(min, max)| BoundingBox::from_minmax(min, max))
+        self.edges()
+            .map(|(a, b)| {
+                let (ab, ap) = (b - a, *point - a);
+                let len2 = ab.x() * ab.x() + ab.y() * ab.y();
+                let t = if 0. < len2 {
+                    ((ab.x() * ap.x() + ab.y() * ap.y()) / len2).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let (dx, dy) = (ap.x() - ab.x() * t, ap.y() - ab.y() * t);
+                dx * dx + dy * dy
+            })
+            .fold(f64::INFINITY, f64::min)
     }
 }
 
 #[derive(Deserialize)]
 pub struct ConvexHulls {
     #[serde(rename = "convex hulls")]
-    pub convex_hulls: Vec<ConvexHull>,
+    pub convex_hulls: Vec<ConvexHullData>,
 }
@@ -5,11 +5,12 @@ use crate::{app::RustreeApp};
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    let mut native_options = eframe::NativeOptions::default();
-
     // We insist to use light theme, because the canvas color is designed to work with light background.
-    native_options.follow_system_theme = false;
-    native_options.default_theme = eframe::Theme::Light;
+    let native_options = eframe::NativeOptions {
+        follow_system_theme: false,
+        default_theme: eframe::Theme::Light,
+        ..Default::default()
+    };
 
     eframe::run_native(
         "rustree GUI",
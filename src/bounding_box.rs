@@ -1,61 +1,233 @@
-use crate::point::Point;
+use crate::{point::Point, scalar::Scalar};
 
+/// An axis-aligned bounding box in `D`-dimensional space. It is 2D by default.
 #[derive(Copy, Clone, Debug)]
-pub struct BoundingBox {
-    pub min: Point,
-    pub max: Point,
+pub struct BoundingBox<S = f64, const D: usize = 2> {
+    pub min: Point<S, D>,
+    pub max: Point<S, D>,
 }
 
-impl BoundingBox {
-    pub fn new(x0: f64, y0: f64, x1: f64, y1: f64) -> Self {
+impl<S: Scalar> BoundingBox<S, 2> {
+    pub fn new(x0: S, y0: S, x1: S, y1: S) -> Self {
         Self {
-            min: Point { x: x0, y: y0 },
-            max: Point { x: x1, y: y1 },
+            min: Point::new(x0, y0),
+            max: Point::new(x1, y1),
         }
     }
-    pub fn from_minmax(min: Point, max: Point) -> Self {
+
+    /// The same as [`BoundingBox::get_volume`] in 2D.
+    pub fn get_area(&self) -> f64 {
+        self.get_volume()
+    }
+}
+
+impl<S: Scalar, const D: usize> BoundingBox<S, D> {
+    pub fn from_minmax(min: Point<S, D>, max: Point<S, D>) -> Self {
         Self { min, max }
     }
 
-    pub fn from_center_size(center: Point, size: Point) -> Self {
+    pub fn from_center_size(center: Point<S, D>, size: Point<S, D>) -> Self {
         Self {
             min: center - size,
             max: center + size,
         }
     }
 
-    pub fn get_union(&self, b: &BoundingBox) -> BoundingBox {
-        return BoundingBox {
-            min: Point {
-                x: f64::min(self.min.x, b.min.x),
-                y: f64::min(self.min.y, b.min.y),
-            },
-            max: Point {
-                x: f64::max(self.max.x, b.max.x),
-                y: f64::max(self.max.y, b.max.y),
-            },
-        };
+    pub fn get_union(&self, b: &Self) -> Self {
+        BoundingBox {
+            min: self.min.zip_map(&b.min, S::min),
+            max: self.max.zip_map(&b.max, S::max),
+        }
     }
 
-    pub fn get_area(&self) -> f64 {
-        return (self.max.x - self.min.x) * (self.max.y - self.min.y);
+    /// The length of the box along each axis, in `f64`.
+    fn extents(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..D).map(|i| (self.max.coords[i] - self.min.coords[i]).to_f64())
+    }
+
+    /// The product of the edge lengths, which is the area in 2D.
+    ///
+    /// It is computed in `f64` regardless of the scalar type, so that it does not overflow with integers.
+    pub fn get_volume(&self) -> f64 {
+        self.extents().product()
+    }
+
+    pub fn get_center(&self) -> Point<S, D> {
+        let two = S::ONE + S::ONE;
+        self.min.zip_map(&self.max, |min, max| (min + max) / two)
+    }
+
+    /// Sum of the edge lengths, which is half of the perimeter in 2D, computed in `f64` like
+    /// [`BoundingBox::get_volume`].
+    pub fn get_margin(&self) -> f64 {
+        self.extents().sum()
+    }
+
+    /// Returns the overlapping region of the two bounding boxes, or `None` if they do not intersect.
+    pub fn get_intersection(&self, b: &Self) -> Option<Self> {
+        if !self.intersects(b) {
+            return None;
+        }
+        Some(BoundingBox {
+            min: self.min.zip_map(&b.min, S::max),
+            max: self.max.zip_map(&b.max, S::min),
+        })
+    }
+
+    /// Returns a bounding box enlarged by `margin` in every direction.
+    pub fn get_expanded(&self, margin: S) -> Self {
+        BoundingBox {
+            min: self.min.zip_map(&self.min, |v, _| v - margin),
+            max: self.max.zip_map(&self.max, |v, _| v + margin),
+        }
+    }
+
+    /// Returns true if `other` is entirely inside this bounding box.
+    pub fn contains(&self, other: &Self) -> bool {
+        (0..D).all(|i| {
+            self.min.coords[i] <= other.min.coords[i] && other.max.coords[i] <= self.max.coords[i]
+        })
+    }
+
+    /// Returns true if `point` is inside this bounding box, including the boundary.
+    pub fn contains_point(&self, point: &Point<S, D>) -> bool {
+        (0..D)
+            .all(|i| self.min.coords[i] <= point.coords[i] && point.coords[i] <= self.max.coords[i])
     }
 
     pub fn intersects(&self, other: &Self) -> bool {
-        self.min.x <= other.max.x
-            && other.min.x <= self.max.x
-            && self.min.y <= other.max.y
-            && other.min.y <= self.max.y
+        (0..D).all(|i| {
+            self.min.coords[i] <= other.max.coords[i] && other.min.coords[i] <= self.max.coords[i]
+        })
+    }
+
+    /// Squared Euclidean distance from `point` to the nearest point of this bounding box in `f64`,
+    /// which is 0 if the point is inside.
+    pub fn distance2_to_point(&self, point: &Point<S, D>) -> f64 {
+        (0..D)
+            .map(|i| {
+                let v = point.coords[i].to_f64();
+                let d = (self.min.coords[i].to_f64() - v)
+                    .max(v - self.max.coords[i].to_f64())
+                    .max(0.);
+                d * d
+            })
+            .sum()
+    }
+
+    /// Slab test of the ray `origin + t * direction` for `0 <= t <= max_t`, computed in `f64`.
+    ///
+    /// Returns the smallest `t` at which the ray is inside the box, which is 0 if the origin is inside,
+    /// or `None` if the ray misses the box within `max_t`.
+    pub fn intersect_ray(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> Option<f64> {
+        let (mut t_enter, mut t_exit) = (0., max_t);
+        for i in 0..D {
+            let (o, d) = (origin.coords[i].to_f64(), direction.coords[i].to_f64());
+            let (min, max) = (self.min.coords[i].to_f64(), self.max.coords[i].to_f64());
+            if d == 0. {
+                // Parallel to the slab
+                if o < min || max < o {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - o) / d, (max - o) / d);
+            t_enter = t0.min(t1).max(t_enter);
+            t_exit = t0.max(t1).min(t_exit);
+            if t_exit < t_enter {
+                return None;
+            }
+        }
+        Some(t_enter)
+    }
+
+    /// Returns true if the segment from `a` to `b` shares any point with this bounding box.
+    pub fn intersects_segment(&self, a: &Point<S, D>, b: &Point<S, D>) -> bool {
+        self.intersect_ray(a, &(*b - *a), 1.).is_some()
+    }
+
+    /// Squared Euclidean distance between the segment from `a` to `b` and this bounding box in `f64`,
+    /// which is 0 if they intersect.
+    pub fn distance2_to_segment(&self, a: &Point<S, D>, b: &Point<S, D>) -> f64 {
+        let a = a.coords.map(S::to_f64);
+        let d: [f64; D] = std::array::from_fn(|i| b.coords[i].to_f64() - a[i]);
+        let (min, max) = (
+            self.min.coords.map(S::to_f64),
+            self.max.coords.map(S::to_f64),
+        );
+        // The squared distance along the segment is a convex piecewise quadratic function of the parameter,
+        // which changes its form only where the segment crosses the planes of the box.
+        let mut ts = vec![0., 1.];
+        for i in 0..D {
+            if d[i] != 0. {
+                ts.extend(
+                    [(min[i] - a[i]) / d[i], (max[i] - a[i]) / d[i]]
+                        .into_iter()
+                        .filter(|t| 0. < *t && *t < 1.),
+                );
+            }
+        }
+        ts.sort_by(f64::total_cmp);
+        ts.windows(2)
+            .map(|w| {
+                // Coefficients of q2 * t^2 + q1 * t + q0 in this interval
+                let mid = (w[0] + w[1]) / 2.;
+                let (mut q2, mut q1, mut q0) = (0., 0., 0.);
+                for i in 0..D {
+                    let v = a[i] + mid * d[i];
+                    let bound = if v < min[i] {
+                        min[i]
+                    } else if max[i] < v {
+                        max[i]
+                    } else {
+                        continue;
+                    };
+                    let offset = a[i] - bound;
+                    q2 += d[i] * d[i];
+                    q1 += 2. * offset * d[i];
+                    q0 += offset * offset;
+                }
+                let t = if 0. < q2 {
+                    (-q1 / (2. * q2)).clamp(w[0], w[1])
+                } else {
+                    w[0]
+                };
+                (q2 * t + q1) * t + q0
+            })
+            .fold(f64::INFINITY, f64::min)
+            .max(0.)
+    }
+
+    /// Converts the coordinates to `f64`, e.g. to feed geometric heuristics.
+    pub fn to_f64(&self) -> BoundingBox<f64, D> {
+        BoundingBox {
+            min: Point::from_coords(self.min.coords.map(S::to_f64)),
+            max: Point::from_coords(self.max.coords.map(S::to_f64)),
+        }
     }
 }
 
-impl std::fmt::Display for BoundingBox {
+impl<S: Scalar, const D: usize> std::fmt::Display for BoundingBox<S, D> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "({}, {}, {}, {})",
-            self.min.x, self.min.y, self.max.x, self.max.y
-        )
+        write!(f, "(")?;
+        for (i, v) in self
+            .min
+            .coords
+            .iter()
+            .chain(self.max.coords.iter())
+            .enumerate()
+        {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{v}")?;
+        }
+        write!(f, ")")
     }
 }
 
@@ -68,3 +240,84 @@ fn test_intersects() {
     let bb4 = BoundingBox::new(1., 1., 2., 2.);
     assert!(!bb3.intersects(&bb4));
 }
+
+#[test]
+fn test_integer_scalar() {
+    let bb1 = BoundingBox::new(0i32, 0, 4, 4);
+    let bb2 = BoundingBox::new(2, 2, 6, 6);
+    let union = bb1.get_union(&bb2);
+    assert_eq!((union.min, union.max), (Point::new(0, 0), Point::new(6, 6)));
+    let intersection = bb1.get_intersection(&bb2).unwrap();
+    assert_eq!(
+        (intersection.min, intersection.max),
+        (Point::new(2, 2), Point::new(4, 4))
+    );
+    assert_eq!(intersection.get_area(), 4.);
+    // Touching boxes intersect exactly with integers
+    assert!(bb1.intersects(&BoundingBox::new(4, 4, 5, 5)));
+    assert!(!bb1.intersects(&BoundingBox::new(5, 5, 6, 6)));
+    // The area does not overflow
+    let large = BoundingBox::new(0i32, 0, i32::MAX, i32::MAX);
+    assert_eq!(large.get_area(), (i32::MAX as f64).powi(2));
+}
+
+#[test]
+fn test_distance2_to_point() {
+    let bb = BoundingBox::new(0., 0., 2., 1.);
+    assert_eq!(bb.distance2_to_point(&Point::new(1., 0.5)), 0.);
+    assert_eq!(bb.distance2_to_point(&Point::new(-3., 0.5)), 9.);
+    assert_eq!(bb.distance2_to_point(&Point::new(5., 5.)), 25.);
+    let bb = BoundingBox::new(0i32, 0, 2, 1);
+    assert_eq!(bb.distance2_to_point(&Point::new(2, 3)), 4.);
+}
+
+#[test]
+fn test_intersect_ray() {
+    let bb = BoundingBox::new(2., 1., 4., 3.);
+    let ray = |x, y, dx, dy, max_t| bb.intersect_ray(&Point::new(x, y), &Point::new(dx, dy), max_t);
+    assert_eq!(ray(0., 2., 1., 0., 10.), Some(2.));
+    assert_eq!(ray(0., 2., 1., 0., 1.5), None);
+    assert_eq!(ray(6., 2., -2., 0., 10.), Some(1.));
+    assert_eq!(ray(3., 2., 1., 1., 10.), Some(0.));
+    assert_eq!(ray(0., 0., 1., 1., 10.), Some(2.));
+    assert_eq!(ray(0., 4., 1., 0., 10.), None);
+    assert_eq!(ray(0., 2., -1., 0., 10.), None);
+    // Grazing the corner
+    assert_eq!(ray(0., -1., 1., 1., 10.), Some(2.));
+}
+
+#[test]
+fn test_segment() {
+    let bb = BoundingBox::new(2., 1., 4., 3.);
+    let segment = |x0, y0, x1, y1| (Point::new(x0, y0), Point::new(x1, y1));
+    let (a, b) = segment(0., 0., 6., 4.);
+    assert!(bb.intersects_segment(&a, &b));
+    assert_eq!(bb.distance2_to_segment(&a, &b), 0.);
+    // The enclosing box of this segment covers the box, but the segment passes by its corner.
+    let (a, b) = segment(0., 3., 3., 6.);
+    assert!(!bb.intersects_segment(&a, &b));
+    assert!((bb.distance2_to_segment(&a, &b) - 2.).abs() < 1e-12);
+    // Ends short of the box
+    let (a, b) = segment(0., 2., 1., 2.);
+    assert!(!bb.intersects_segment(&a, &b));
+    assert_eq!(bb.distance2_to_segment(&a, &b), 1.);
+    // A point
+    let (a, b) = segment(5., 5., 5., 5.);
+    assert_eq!(bb.distance2_to_segment(&a, &b), 5.);
+    let (a, b) = segment(3., 2., 3., 2.);
+    assert!(bb.intersects_segment(&a, &b));
+}
+
+#[test]
+fn test_3d() {
+    let bb1 = BoundingBox::from_minmax(Point::from([0., 0., 0.]), Point::from([2., 3., 4.]));
+    let bb2 = BoundingBox::from_minmax(Point::from([1., 1., 3.]), Point::from([5., 5., 5.]));
+    assert_eq!(bb1.get_volume(), 24.);
+    assert_eq!(bb1.get_margin(), 9.);
+    assert!(bb1.intersects(&bb2));
+    assert_eq!(bb1.get_intersection(&bb2).unwrap().get_volume(), 2.);
+    // Overlapping in x and y, but not in z
+    let bb3 = BoundingBox::from_minmax(Point::from([1., 1., 5.]), Point::from([2., 2., 6.]));
+    assert!(!bb1.intersects(&bb3));
+    assert_eq!(format!("{bb1}"), "(0, 0, 0, 2, 3, 4)");
+}
@@ -1,9 +1,18 @@
 mod bounding_box;
+mod object;
 mod point;
 mod r_tree;
+mod scalar;
 
 pub use crate::{
     bounding_box::BoundingBox,
+    object::{ExactGeometry, PointDistance, RTreeObject, RayIntersection},
     point::Point,
-    r_tree::{RTree, RTreeEntry, RTreeNode, WalkCallbackPayload},
+    r_tree::{
+        CompactOrder, EntryId, ExhaustiveSplit, InsertionMode, IntoIter, LinearSplit,
+        QuadraticSplit, RStarSplit, RTree, RTreeEntry, RTreeNode, RTreeParams, SelectContainedBy,
+        SelectContains, SelectDisjoint, SelectIntersects, SelectTouchesPoint, SelectionFunction,
+        SplitStrategy, WalkCallbackPayload,
+    },
+    scalar::Scalar,
 };
@@ -0,0 +1,116 @@
+use crate::{bounding_box::BoundingBox, point::Point, scalar::Scalar};
+
+/// An object that knows its own bounding box, so that it can be put in an [`RTree`](crate::RTree) with
+/// [`RTree::insert`](crate::RTree::insert) without computing the box separately.
+///
+/// The envelope must bound the whole object, and it must not change while the object is in the tree.
+pub trait RTreeObject<S: Scalar = f64, const D: usize = 2> {
+    /// The smallest bounding box that contains the object.
+    fn envelope(&self) -> BoundingBox<S, D>;
+}
+
+impl<S: Scalar, const D: usize> RTreeObject<S, D> for Point<S, D> {
+    /// A point is a degenerate box with no extent.
+    fn envelope(&self) -> BoundingBox<S, D> {
+        BoundingBox::from_minmax(*self, *self)
+    }
+}
+
+impl<S: Scalar, const D: usize> RTreeObject<S, D> for BoundingBox<S, D> {
+    fn envelope(&self) -> BoundingBox<S, D> {
+        *self
+    }
+}
+
+impl<S: Scalar, const D: usize, O: RTreeObject<S, D> + ?Sized> RTreeObject<S, D> for Box<O> {
+    fn envelope(&self) -> BoundingBox<S, D> {
+        (**self).envelope()
+    }
+}
+
+/// An object that can measure its own distance to a point, used by
+/// [`RTree::locate_within_distance`](crate::RTree::locate_within_distance) to check entries exactly
+/// rather than by their bounding boxes.
+pub trait PointDistance<S: Scalar = f64, const D: usize = 2> {
+    /// Squared Euclidean distance from `point` to the nearest point of the object, in `f64`.
+    /// It should be 0 if the object contains the point.
+    fn distance2_to_point(&self, point: &Point<S, D>) -> f64;
+}
+
+impl<S: Scalar, const D: usize> PointDistance<S, D> for Point<S, D> {
+    fn distance2_to_point(&self, point: &Point<S, D>) -> f64 {
+        BoundingBox::from_minmax(*self, *self).distance2_to_point(point)
+    }
+}
+
+impl<S: Scalar, const D: usize> PointDistance<S, D> for BoundingBox<S, D> {
+    fn distance2_to_point(&self, point: &Point<S, D>) -> f64 {
+        BoundingBox::distance2_to_point(self, point)
+    }
+}
+
+/// The exact shape of an object, for queries that first filter the entries by their bounding boxes and then
+/// refine the candidates, such as [`RTree::find_multi_exact`](crate::RTree::find_multi_exact).
+pub trait ExactGeometry<S: Scalar = f64, const D: usize = 2> {
+    /// Returns true if the object shares any point with the bounding box.
+    fn intersects_box(&self, bounding_box: &BoundingBox<S, D>) -> bool;
+
+    /// Returns true if the point is inside the object, including the boundary.
+    fn contains_point(&self, point: &Point<S, D>) -> bool;
+
+    /// Euclidean distance from `point` to the nearest point of the object, which is 0 if it contains the point.
+    /// It must not be less than the distance to the bounding box of the object.
+    fn distance_to_point(&self, point: &Point<S, D>) -> f64;
+}
+
+impl<S: Scalar, const D: usize> ExactGeometry<S, D> for Point<S, D> {
+    fn intersects_box(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.contains_point(self)
+    }
+
+    fn contains_point(&self, point: &Point<S, D>) -> bool {
+        self == point
+    }
+
+    fn distance_to_point(&self, point: &Point<S, D>) -> f64 {
+        PointDistance::distance2_to_point(self, point).sqrt()
+    }
+}
+
+impl<S: Scalar, const D: usize> ExactGeometry<S, D> for BoundingBox<S, D> {
+    fn intersects_box(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        self.intersects(bounding_box)
+    }
+
+    fn contains_point(&self, point: &Point<S, D>) -> bool {
+        BoundingBox::contains_point(self, point)
+    }
+
+    fn distance_to_point(&self, point: &Point<S, D>) -> f64 {
+        self.distance2_to_point(point).sqrt()
+    }
+}
+
+/// The exact intersection of an object with a ray, used by [`RTree::raycast_exact`](crate::RTree::raycast_exact)
+/// to refine the hits found by the bounding boxes.
+pub trait RayIntersection<S: Scalar = f64, const D: usize = 2> {
+    /// Returns the smallest `t` in `0..=max_t` at which the ray `origin + t * direction` hits the object,
+    /// or `None` if it does not. It must not be less than the `t` at which the ray enters the bounding box.
+    fn intersect_ray(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> Option<f64>;
+}
+
+impl<S: Scalar, const D: usize> RayIntersection<S, D> for BoundingBox<S, D> {
+    fn intersect_ray(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> Option<f64> {
+        BoundingBox::intersect_ray(self, origin, direction, max_t)
+    }
+}
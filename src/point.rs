@@ -1,47 +1,113 @@
-use std::f64::EPSILON;
-
+use crate::scalar::Scalar;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, Copy)]
-pub struct Point {
-    pub x: f64,
-    pub y: f64,
+/// A point in `D`-dimensional space. It is 2D by default.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(
+    try_from = "PointRepr<S>",
+    bound(deserialize = "S: Deserialize<'de> + Copy")
+)]
+pub struct Point<S = f64, const D: usize = 2> {
+    pub coords: [S; D],
+}
+
+impl<S: Scalar, const D: usize> Point<S, D> {
+    pub fn from_coords(coords: [S; D]) -> Self {
+        Self { coords }
+    }
+
+    /// Applies `f` to each pair of coordinates of 2 points.
+    pub(crate) fn zip_map(&self, other: &Self, f: impl Fn(S, S) -> S) -> Self {
+        Self {
+            coords: std::array::from_fn(|i| f(self.coords[i], other.coords[i])),
+        }
+    }
+}
+
+impl<S: Scalar> Point<S, 2> {
+    pub fn new(x: S, y: S) -> Self {
+        Self { coords: [x, y] }
+    }
+
+    pub fn x(&self) -> S {
+        self.coords[0]
+    }
+
+    pub fn y(&self) -> S {
+        self.coords[1]
+    }
 }
 
-impl Point {
-    pub fn new(x: f64, y: f64) -> Self {
-        Self { x, y }
+impl<S: Scalar> Point<S, 3> {
+    pub fn x(&self) -> S {
+        self.coords[0]
+    }
+
+    pub fn y(&self) -> S {
+        self.coords[1]
+    }
+
+    pub fn z(&self) -> S {
+        self.coords[2]
     }
 }
 
-impl Clone for Point {
-    fn clone(&self) -> Self {
-        *self
+impl<S: Scalar, const D: usize> From<[S; D]> for Point<S, D> {
+    fn from(coords: [S; D]) -> Self {
+        Self { coords }
     }
 }
 
-impl PartialEq for Point {
+impl<S: Scalar, const D: usize> PartialEq for Point<S, D> {
     fn eq(&self, other: &Self) -> bool {
-        f64::abs(self.x - other.x) < EPSILON && f64::abs(self.y - other.y) < EPSILON
+        self.coords
+            .iter()
+            .zip(other.coords.iter())
+            .all(|(a, b)| a.approx_eq(*b))
     }
 }
 
-impl std::ops::Add for Point {
+impl<S: Scalar, const D: usize> std::ops::Add for Point<S, D> {
     type Output = Self;
-    fn add(self, other: Point) -> Point {
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
+    fn add(self, other: Point<S, D>) -> Point<S, D> {
+        self.zip_map(&other, |a, b| a + b)
     }
 }
 
-impl std::ops::Sub for Point {
+impl<S: Scalar, const D: usize> std::ops::Sub for Point<S, D> {
     type Output = Self;
-    fn sub(self, other: Point) -> Point {
-        Self {
-            x: self.x - other.x,
-            y: self.y - other.y,
-        }
+    fn sub(self, other: Point<S, D>) -> Point<S, D> {
+        self.zip_map(&other, |a, b| a - b)
+    }
+}
+
+/// The serialized forms of a point: either named coordinates like `{"x": 1, "y": 2}`, or an array `[1, 2]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PointRepr<S> {
+    Named {
+        x: S,
+        y: Option<S>,
+        z: Option<S>,
+        w: Option<S>,
+    },
+    Array(Vec<S>),
+}
+
+impl<S: Copy, const D: usize> TryFrom<PointRepr<S>> for Point<S, D> {
+    type Error = String;
+    fn try_from(repr: PointRepr<S>) -> Result<Self, Self::Error> {
+        let coords: Vec<S> = match repr {
+            PointRepr::Named { x, y, z, w } => std::iter::once(Some(x))
+                .chain([y, z, w])
+                .map_while(|v| v)
+                .collect(),
+            PointRepr::Array(coords) => coords,
+        };
+        let len = coords.len();
+        coords
+            .try_into()
+            .map(|coords| Self { coords })
+            .map_err(|_| format!("expected a point of {D} dimensions, got {len}"))
     }
 }
@@ -1,11 +1,32 @@
+mod bulk_load;
+mod bulk_remove;
+mod compact;
+mod entry_id;
+mod iter;
+mod locate;
+mod merge;
+mod nearest;
+mod params;
+mod raycast;
+mod selection;
+mod split;
 mod walk;
 
-pub use self::walk::WalkCallbackPayload;
-use crate::{bounding_box::BoundingBox, point::Point};
+pub use self::{
+    compact::CompactOrder,
+    entry_id::EntryId,
+    iter::IntoIter,
+    params::{InsertionMode, RTreeParams},
+    selection::{
+        SelectContainedBy, SelectContains, SelectDisjoint, SelectIntersects, SelectTouchesPoint,
+        SelectionFunction,
+    },
+    split::{ExhaustiveSplit, LinearSplit, QuadraticSplit, RStarSplit, SplitStrategy},
+    walk::WalkCallbackPayload,
+};
+use crate::{bounding_box::BoundingBox, object::RTreeObject, point::Point, scalar::Scalar};
 use std::{fmt::Debug, io::Write};
 
-const M: usize = 4;
-
 #[derive(Debug)]
 pub enum RTreeNode<T> {
     Node(Vec<usize>),
@@ -13,86 +34,172 @@ pub enum RTreeNode<T> {
 }
 
 #[derive(Debug)]
-pub struct RTreeEntry<T> {
-    bb: BoundingBox,
+pub struct RTreeEntry<T, S = f64, const D: usize = 2> {
+    bb: BoundingBox<S, D>,
     parent: Option<usize>,
+    /// The stable id of a leaf entry. Always `None` for an internal node.
+    id: Option<EntryId>,
     node: RTreeNode<T>,
 }
 
-impl<T> RTreeEntry<T> {
-    pub fn bounding_box(&self) -> &BoundingBox {
+impl<T, S, const D: usize> RTreeEntry<T, S, D> {
+    pub fn bounding_box(&self) -> &BoundingBox<S, D> {
         &self.bb
     }
 
     pub fn node(&self) -> &RTreeNode<T> {
         &self.node
     }
+
+    /// The stable id of this entry if it is a leaf.
+    pub fn id(&self) -> Option<EntryId> {
+        self.id
+    }
 }
 
 #[derive(Debug)]
-pub struct RTree<T> {
-    nodes: Vec<RTreeEntry<T>>,
+pub struct RTree<T, S = f64, const D: usize = 2> {
+    nodes: Vec<RTreeEntry<T, S, D>>,
+    /// Vacant slots in `nodes` to be reused before growing it.
+    free_nodes: Vec<usize>,
+    ids: Vec<entry_id::IdSlot>,
+    free_ids: Vec<u32>,
     max_depth: usize,
+    /// The number of leaf entries.
+    len: usize,
+    params: RTreeParams<D>,
 }
 
-impl<T: Debug> RTree<T> {
+impl<T: Debug, S: Scalar, const D: usize> Default for RTree<T, S, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
     pub fn new() -> Self {
+        Self::with_params(RTreeParams::default())
+    }
+
+    /// Creates an empty RTree with the given node capacity.
+    ///
+    /// Panics if the parameters are inconsistent, e.g. the minimum fill is more than half the maximum.
+    pub fn with_params(params: RTreeParams<D>) -> Self {
+        params.validate();
         Self {
-            nodes: vec![RTreeEntry {
-                bb: BoundingBox {
-                    min: Point { x: -1., y: -1. },
-                    max: Point { x: 1., y: 1. },
-                },
-                parent: None,
-                node: RTreeNode::Node(vec![]),
-            }],
+            nodes: vec![Self::empty_root()],
+            free_nodes: vec![],
+            ids: vec![],
+            free_ids: vec![],
             max_depth: 1,
+            len: 0,
+            params,
+        }
+    }
+
+    fn empty_root() -> RTreeEntry<T, S, D> {
+        RTreeEntry {
+            // The root of an empty tree has no meaningful bounding box. This placeholder is never exposed
+            // and is overwritten by the first insertion.
+            bb: BoundingBox::from_minmax(Point::from([S::ZERO; D]), Point::from([S::ZERO; D])),
+            parent: None,
+            id: None,
+            node: RTreeNode::Node(vec![]),
         }
     }
 
-    pub fn bounding_box(&self) -> BoundingBox {
-        self.nodes[0].bb
+    pub fn params(&self) -> &RTreeParams<D> {
+        &self.params
+    }
+
+    /// Returns the bounding box of all the entries, or `None` if the tree is empty.
+    pub fn bounding_box(&self) -> Option<BoundingBox<S, D>> {
+        (!self.is_empty()).then_some(self.nodes[0].bb)
+    }
+
+    /// Returns the number of entries in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     pub fn max_depth(&self) -> usize {
         self.max_depth
     }
 
-    fn choose_leaf_rec(
-        &self,
-        node: usize,
-        bounding_box: &BoundingBox,
-        level: usize,
-    ) -> (usize, usize) {
-        if matches!(self.nodes[node].node, RTreeNode::Leaf(_)) {
-            return (node, level);
-        }
-        let min = if let RTreeNode::Node(children) = &self.nodes[node].node {
-            children
-                .iter()
-                .filter(|child| matches!(self.nodes[**child].node, RTreeNode::Node(_)))
-                .min_by(|a, b| {
-                    let area_a = self.nodes[**a].bb.get_union(bounding_box).get_area();
-                    let area_b = self.nodes[**b].bb.get_union(bounding_box).get_area();
+    /// Descends from the root to the node at `height` that should receive an entry with `bounding_box`.
+    ///
+    /// Heights are counted from the bottom: leaf entries are at 0 and the root is at `max_depth`.
+    fn choose_subtree(&self, bounding_box: &BoundingBox<S, D>, height: usize) -> usize {
+        let mut node = 0;
+        let mut node_height = self.max_depth;
+        while height < node_height {
+            let RTreeNode::Node(children) = &self.nodes[node].node else {
+                panic!("Descending into a leaf!!")
+            };
+            let rstar = self.params.insertion == InsertionMode::RStar;
+            let chosen = if rstar && node_height == 2 {
+                // R* minimizes the overlap enlargement when the children hold leaf entries.
+                children.iter().min_by(|a, b| {
+                    let key = |id: usize| {
+                        let bb = &self.nodes[id].bb;
+                        let union = bb.get_union(bounding_box);
+                        let overlap_enlargement = children
+                            .iter()
+                            .filter(|other| **other != id)
+                            .map(|other| {
+                                let other = &self.nodes[*other].bb;
+                                let overlap = |bb: &BoundingBox<S, D>| {
+                                    bb.get_intersection(other)
+                                        .map(|i| i.get_volume())
+                                        .unwrap_or(0.)
+                                };
+                                overlap(&union) - overlap(bb)
+                            })
+                            .sum::<f64>();
+                        (
+                            overlap_enlargement,
+                            union.get_volume() - bb.get_volume(),
+                            bb.get_volume(),
+                        )
+                    };
+                    let (a, b) = (key(**a), key(**b));
+                    a.0.total_cmp(&b.0)
+                        .then(a.1.total_cmp(&b.1))
+                        .then(a.2.total_cmp(&b.2))
+                })
+            } else if rstar {
+                children.iter().min_by(|a, b| {
+                    let key = |id: usize| {
+                        let bb = &self.nodes[id].bb;
+                        (
+                            bb.get_union(bounding_box).get_volume() - bb.get_volume(),
+                            bb.get_volume(),
+                        )
+                    };
+                    let (a, b) = (key(**a), key(**b));
+                    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+                })
+            } else {
+                children.iter().min_by(|a, b| {
+                    let area_a = self.nodes[**a].bb.get_union(bounding_box).get_volume();
+                    let area_b = self.nodes[**b].bb.get_union(bounding_box).get_volume();
                     area_a.partial_cmp(&area_b).unwrap()
                 })
-                .cloned()
-        } else {
-            None
-        };
-        if let Some(min) = min {
-            self.choose_leaf_rec(min, bounding_box, level + 1)
-        } else {
-            (node, level)
+            };
+            let Some(chosen) = chosen else {
+                break;
+            };
+            node = *chosen;
+            node_height -= 1;
         }
+        node
     }
 
-    /// Returnst (id, level)
-    fn choose_leaf(&self, this: usize, bounding_box: &BoundingBox) -> (usize, usize) {
-        self.choose_leaf_rec(this, bounding_box, 0)
-    }
-
-    fn find_rec(&self, this: usize, bounding_box: &BoundingBox) -> Option<&T> {
+    fn find_rec(&self, this: usize, bounding_box: &BoundingBox<S, D>) -> Option<&T> {
         // println!(
         //     "nodes[{this}].intersects({}, {bounding_box}) => {}",
         //     self.nodes[this].bb,
@@ -112,20 +219,20 @@ impl<T: Debug> RTree<T> {
 
     /// Finds an entry from this RTree that intersects with the given bounding box.
     /// It returns only the first found item.
-    pub fn find(&self, bounding_box: &BoundingBox) -> Option<&T> {
+    pub fn find(&self, bounding_box: &BoundingBox<S, D>) -> Option<&T> {
         self.find_rec(0, bounding_box)
     }
 
-    pub fn find_multi(&self, bounding_box: &BoundingBox) -> impl Iterator<Item = &T> {
-        struct Finder<'a, T> {
-            this: &'a RTree<T>,
-            bb: BoundingBox,
+    pub fn find_multi(&self, bounding_box: &BoundingBox<S, D>) -> impl Iterator<Item = &T> {
+        struct Finder<'a, T, S, const D: usize> {
+            this: &'a RTree<T, S, D>,
+            bb: BoundingBox<S, D>,
             /// (Node id, child index)
             stack: Vec<(usize, usize)>,
         }
 
-        impl<'a, T> Finder<'a, T> {
-            fn new(this: &'a RTree<T>, bb: BoundingBox) -> Self {
+        impl<'a, T, S: Scalar, const D: usize> Finder<'a, T, S, D> {
+            fn new(this: &'a RTree<T, S, D>, bb: BoundingBox<S, D>) -> Self {
                 Self {
                     this,
                     bb,
@@ -135,9 +242,7 @@ impl<T: Debug> RTree<T> {
 
             fn find_multi(&mut self) -> Option<&'a T> {
                 loop {
-                    let Some((node, child)) = self.stack.pop() else {
-                        return None;
-                    };
+                    let (node, child) = self.stack.pop()?;
                     if self.this.nodes[node].bb.intersects(&self.bb) {
                         match self.this.nodes[node].node {
                             RTreeNode::Leaf(ref leaf) => return Some(leaf),
@@ -153,7 +258,7 @@ impl<T: Debug> RTree<T> {
             }
         }
 
-        impl<'a, T> Iterator for Finder<'a, T> {
+        impl<'a, T, S: Scalar, const D: usize> Iterator for Finder<'a, T, S, D> {
             type Item = &'a T;
             fn next(&mut self) -> Option<Self::Item> {
                 self.find_multi()
@@ -163,169 +268,358 @@ impl<T: Debug> RTree<T> {
         Finder::new(self, *bounding_box)
     }
 
-    pub fn adjust_tree(&mut self, node: usize, nodes_to_add: &mut Vec<RTreeEntry<T>>) {
-        let node_bb = self.nodes[node].bb;
-        match self.nodes[node].parent {
-            None => (),
-            Some(p) => {
-                let mut bb = node_bb;
-                let size = match &self.nodes[node].node {
-                    RTreeNode::Leaf(_) => 0,
-                    RTreeNode::Node(children) => {
-                        for child in children {
-                            bb = bb.get_union(&self.nodes[*child].bb);
-                        }
-                        children.len()
-                    }
-                };
-
-                let parent = &mut self.nodes[p];
-                parent.bb = bb;
-
-                if !nodes_to_add.is_empty() {
-                    if size < M {
-                        let parent = &mut self.nodes[p];
-                        let mut node_to_add = nodes_to_add.remove(0);
-                        node_to_add.parent = Some(p);
-                        parent.bb = parent.bb.get_union(&node_to_add.bb);
-                        let idx = self.nodes.len();
-                        self.nodes.push(node_to_add);
-                        match &mut self.nodes[node].node {
-                            RTreeNode::Leaf(_) => (),
-                            RTreeNode::Node(children) => {
-                                children.push(idx);
-                            }
-                        }
-                    } else {
-                        todo!()
-                    }
+    /// Adds `child` to `node`, which is at `height`, and propagates the structural changes up to the root.
+    ///
+    /// An overflowing node is split and the new sibling is added to the parent, which is split in turn
+    /// if it overflows. Only a split of the root adds a level, so every leaf stays at the same depth.
+    /// In R* mode, the first overflow at each height in an insertion does a forced reinsertion instead.
+    /// `reinserted` records those heights.
+    ///
+    /// Returns the node whose bounding box and those of its ancestors have not been updated yet to include
+    /// `child`, or `None` if they already are.
+    fn add_child(
+        &mut self,
+        mut node: usize,
+        mut child: usize,
+        mut height: usize,
+        reinserted: &mut Vec<usize>,
+    ) -> Option<usize> {
+        loop {
+            self.nodes[child].parent = Some(node);
+            let size = match &mut self.nodes[node].node {
+                RTreeNode::Leaf(_) => panic!("Adding to leaf!!"),
+                RTreeNode::Node(children) => {
+                    children.push(child);
+                    children.len()
                 }
-                self.adjust_tree(p, nodes_to_add);
+            };
+            if size <= self.params.max_children {
+                break;
             }
+            if self.params.insertion == InsertionMode::RStar
+                && node != 0
+                && !reinserted.contains(&height)
+            {
+                reinserted.push(height);
+                self.force_reinsert(node, height, reinserted);
+                return None;
+            }
+            let sibling = self.split_node(node);
+            let Some(parent) = self.nodes[node].parent else {
+                self.grow_root(sibling);
+                return None;
+            };
+            node = parent;
+            child = sibling;
+            height += 1;
         }
+        Some(node)
     }
 
-    fn append_entry(&mut self, node: RTreeEntry<T>) -> usize {
-        let idx = self.nodes.len();
-        self.nodes.push(node);
-        idx
+    /// Refits the bounding boxes of `node` and all its ancestors.
+    fn refit_upward(&mut self, node: usize) {
+        let mut node = Some(node);
+        while let Some(n) = node {
+            self.refit_bb(n);
+            node = self.nodes[n].parent;
+        }
     }
 
-    fn update_bbox(&mut self, idx: usize, bounding_box: BoundingBox) {
-        let mut parent = Some(idx);
-        let mut bb = bounding_box;
-        while let Some(p) = parent {
-            let parent_node = &mut self.nodes[p];
-            bb = parent_node.bb.get_union(&bb);
-            parent_node.bb = bb;
-            parent = parent_node.parent;
+    /// R* forced reinsertion. Takes out the children of an overflowing node that are farthest from its center
+    /// and inserts them again, which often finds them a better place than splitting would.
+    fn force_reinsert(&mut self, node: usize, height: usize, reinserted: &mut Vec<usize>) {
+        self.refit_bb(node);
+        let center = self.nodes[node].bb.to_f64().get_center();
+        let mut children = match &mut self.nodes[node].node {
+            RTreeNode::Leaf(_) => panic!("Reinserting from a leaf!!"),
+            RTreeNode::Node(children) => std::mem::take(children),
+        };
+        let distance2 = |id: &usize| {
+            let d = self.nodes[*id].bb.to_f64().get_center() - center;
+            d.coords.iter().map(|v| v * v).sum::<f64>()
+        };
+        children.sort_by(|a, b| distance2(b).total_cmp(&distance2(a)));
+        let count = (self.params.max_children * 3 / 10).max(1);
+        let removed: Vec<_> = children.drain(..count).collect();
+        self.nodes[node].node = RTreeNode::Node(children);
+        self.refit_upward(node);
+
+        // Closest first, which is reported to work better than farthest first.
+        for child in removed.into_iter().rev() {
+            self.nodes[child].parent = None;
+            self.insert_subtree(child, height - 1, reinserted);
         }
     }
 
-    /// Update cache of max depth from a subtree. You can start from the middle of a tree. You need to give the correct starting level.
+    /// Makes a new root that holds the old root and its split sibling.
     ///
-    /// This crate does not cache the depth of a node inside each node payload to save space and also remove maintenance cost
-    /// when the node is split into children, so we need to update the cached max depth every time we may have changed the tree.
-    fn update_max_depth(max_depth: &mut usize, nodes: &[RTreeEntry<T>], id: usize, level: usize) {
-        *max_depth = (*max_depth).max(level);
-        match &nodes[id].node {
-            RTreeNode::Node(node) => {
-                for child in node {
-                    Self::update_max_depth(max_depth, nodes, *child, level + 1);
-                }
+    /// The root always lives at index 0, so the old root is moved to a new slot.
+    fn grow_root(&mut self, sibling: usize) {
+        let bb = self.nodes[0].bb.get_union(&self.nodes[sibling].bb);
+        let old_root = std::mem::replace(
+            &mut self.nodes[0],
+            RTreeEntry {
+                bb,
+                parent: None,
+                id: None,
+                node: RTreeNode::Node(vec![]),
+            },
+        );
+        let moved = self.append_entry(old_root);
+        if let RTreeNode::Node(children) = &self.nodes[moved].node {
+            for child in children.clone() {
+                self.nodes[child].parent = Some(moved);
             }
-            _ => {}
         }
+        self.nodes[moved].parent = Some(0);
+        self.nodes[sibling].parent = Some(0);
+        self.nodes[0].node = RTreeNode::Node(vec![moved, sibling]);
+        self.max_depth += 1;
+    }
+
+    fn append_entry(&mut self, node: RTreeEntry<T, S, D>) -> usize {
+        if let Some(idx) = self.free_nodes.pop() {
+            self.nodes[idx] = node;
+            return idx;
+        }
+        let idx = self.nodes.len();
+        self.nodes.push(node);
+        idx
     }
 
     /// Insert an entry object of type T in this RTree with an associated bounding box.
     ///
     /// There is no built-in mechanism to ensure `bounding_box` is actually bounding `value`.
-    /// It is the caller's responsibility to hold that precondition.
-    pub fn insert_entry(&mut self, value: T, bounding_box: BoundingBox) {
-        let (chosen_leaf_i, level) = self.choose_leaf(0, &bounding_box);
-
-        let node_to_add = RTreeEntry {
-            bb: bounding_box,
-            parent: Some(chosen_leaf_i),
-            node: RTreeNode::Leaf(value),
-        };
+    /// It is the caller's responsibility to hold that precondition, or use [`RTree::insert`] instead.
+    ///
+    /// Returns a stable id to access the entry later.
+    pub fn insert_entry(&mut self, value: T, bounding_box: BoundingBox<S, D>) -> EntryId {
+        let (idx, id) = self.append_leaf(value, bounding_box);
+        self.insert_node(idx);
+        self.len += 1;
+        id
+    }
 
-        let idx = self.nodes.len();
-        self.nodes.push(node_to_add);
-        let chosen_leaf = &mut self.nodes[chosen_leaf_i];
-        let node = &mut chosen_leaf.node;
+    /// Insert an object that provides its own bounding box with [`RTreeObject::envelope`].
+    ///
+    /// Unlike [`RTree::insert_entry`], the stored box always matches the object.
+    pub fn insert(&mut self, value: T) -> EntryId
+    where
+        T: RTreeObject<S, D>,
+    {
+        let bounding_box = value.envelope();
+        self.insert_entry(value, bounding_box)
+    }
 
-        let children = match node {
-            RTreeNode::Leaf(_) => panic!("Adding to leaf!!"),
-            RTreeNode::Node(children) => children,
-        };
+    /// Attach an already allocated leaf node to the tree. Used by both fresh insertion and reinsertion of orphans.
+    fn insert_node(&mut self, idx: usize) {
+        self.insert_subtree(idx, 0, &mut vec![]);
+    }
 
-        children.push(idx);
-        if children.len() <= M {
-            self.update_bbox(chosen_leaf_i, bounding_box);
-            Self::update_max_depth(&mut self.max_depth, &self.nodes, chosen_leaf_i, level);
-            return;
+    /// Attach a detached subtree of the given height (0 for a leaf) to a node one level above it.
+    fn insert_subtree(&mut self, idx: usize, height: usize, reinserted: &mut Vec<usize>) {
+        let bounding_box = self.nodes[idx].bb;
+        let parent = self.choose_subtree(&bounding_box, height + 1);
+        if let Some(node) = self.add_child(parent, idx, height + 1, reinserted) {
+            self.refit_upward(node);
         }
+    }
 
-        // Our goal here is to split the collection of nodes into 2 groups that minimizes each bounding box.
-        // I don't know if there is any fast way to find the solution, so I will just scan all possible combinations.
-        let next_children = std::mem::take(children);
-        let all_combi = 1 << next_children.len();
-        // combi represents the combinations of node sets in a bitfield. If a bit is on, it is right side.
-        // We skip 0 and all_combi-1 because it means all nodes are on one side.
-        // Technically, it counts the same combination twice if you flip all bits.
-        let combi = (1..all_combi - 1)
-            .map(|combi| {
-                let left_bb = next_children
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| combi & (1 << *i) == 0)
-                    .map(|(_, id)| self.nodes[*id].bb)
-                    .reduce(|a, b| a.get_union(&b));
-                let right_bb = next_children
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| combi & (1 << *i) != 0)
-                    .map(|(_, id)| self.nodes[*id].bb)
-                    .reduce(|a, b| a.get_union(&b));
-                (
-                    combi,
-                    left_bb.map(|bb| bb.get_area()).unwrap_or(0.)
-                        + right_bb.map(|bb| bb.get_area()).unwrap_or(0.),
-                )
-            })
-            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
-        let Some((combi, _)) = combi else {
-            panic!("No combination is found")
+    /// Splits an overflowing node in two. The node keeps one group of children and a new sibling node
+    /// takes the other. Returns the index of the new sibling, which is not attached to the parent yet.
+    fn split_node(&mut self, node: usize) -> usize {
+        let next_children = match &mut self.nodes[node].node {
+            RTreeNode::Leaf(_) => panic!("Splitting a leaf!!"),
+            RTreeNode::Node(children) => std::mem::take(children),
         };
-        let mut build_child = |f| {
-            let children: Vec<_> = next_children
+
+        let bbs: Vec<_> = next_children
+            .iter()
+            .map(|id| self.nodes[*id].bb.to_f64())
+            .collect();
+        let partition = self
+            .params
+            .split_strategy
+            .split(&bbs, self.params.min_children);
+        let (right, left): (Vec<_>, Vec<_>) = next_children
+            .iter()
+            .zip(partition)
+            .partition(|(_, side)| *side);
+        let left: Vec<_> = left.into_iter().map(|(id, _)| *id).collect();
+        let right: Vec<_> = right.into_iter().map(|(id, _)| *id).collect();
+
+        let sibling = self.append_entry(RTreeEntry {
+            bb: self.nodes[node].bb,
+            parent: None,
+            id: None,
+            node: RTreeNode::Node(vec![]),
+        });
+        for child in &right {
+            self.nodes[*child].parent = Some(sibling);
+        }
+        self.nodes[node].node = RTreeNode::Node(left);
+        self.nodes[sibling].node = RTreeNode::Node(right);
+        self.refit_bb(node);
+        self.refit_bb(sibling);
+        sibling
+    }
+
+    fn find_leaf_rec(
+        &self,
+        this: usize,
+        bounding_box: &BoundingBox<S, D>,
+        pred: &mut impl FnMut(&T) -> bool,
+    ) -> Option<usize> {
+        if !self.nodes[this].bb.intersects(bounding_box) {
+            return None;
+        }
+        match self.nodes[this].node {
+            RTreeNode::Leaf(ref leaf) => pred(leaf).then_some(this),
+            RTreeNode::Node(ref children) => children
                 .iter()
-                .enumerate()
-                .filter(|(i, _)| f ^ (combi & (1 << *i) == 0))
-                .map(|(_, id)| *id)
-                .collect();
-            for child in &children {
-                self.nodes[*child].parent = Some(self.nodes.len());
-            }
-            let bb = children
+                .find_map(|c| self.find_leaf_rec(*c, bounding_box, pred)),
+        }
+    }
+
+    /// Removes the first entry that intersects with the given bounding box and satisfies `pred`, and returns it.
+    ///
+    /// Ancestor bounding boxes are shrunk to fit the remaining entries. Nodes that become underfull are dissolved
+    /// and their remaining entries are reinserted, so the tree keeps its shape after many removals.
+    pub fn remove(
+        &mut self,
+        bounding_box: &BoundingBox<S, D>,
+        mut pred: impl FnMut(&T) -> bool,
+    ) -> Option<T> {
+        let leaf = self.find_leaf_rec(0, bounding_box, &mut pred)?;
+        let parent = self.nodes[leaf].parent?;
+        self.detach_child(parent, leaf);
+        let entry = self.free_node(leaf);
+        if let Some(id) = entry.id {
+            self.release_id(id);
+        }
+        self.len -= 1;
+        self.condense_tree(vec![parent]);
+        match entry.node {
+            RTreeNode::Leaf(value) => Some(value),
+            RTreeNode::Node(_) => None,
+        }
+    }
+
+    fn detach_child(&mut self, parent: usize, child: usize) {
+        if let RTreeNode::Node(children) = &mut self.nodes[parent].node {
+            children.retain(|c| *c != child);
+        }
+        self.nodes[child].parent = None;
+    }
+
+    /// Takes the entry out of the arena. The slot is left vacant as a detached empty node
+    /// and put in the free list to be reused.
+    fn free_node(&mut self, idx: usize) -> RTreeEntry<T, S, D> {
+        self.free_nodes.push(idx);
+        let bb = self.nodes[idx].bb;
+        std::mem::replace(
+            &mut self.nodes[idx],
+            RTreeEntry {
+                bb,
+                parent: None,
+                id: None,
+                node: RTreeNode::Node(vec![]),
+            },
+        )
+    }
+
+    /// Shrinks the bounding box of a node to the union of its children.
+    /// An empty node keeps its bounding box.
+    fn refit_bb(&mut self, idx: usize) {
+        if let RTreeNode::Node(children) = &self.nodes[idx].node {
+            if let Some(bb) = children
                 .iter()
-                .map(|id| self.nodes[*id].bb)
+                .map(|c| self.nodes[*c].bb)
                 .reduce(|a, b| a.get_union(&b))
-                .unwrap();
-            self.append_entry(RTreeEntry {
-                bb,
-                parent: Some(chosen_leaf_i),
-                node: RTreeNode::Node(children),
-            })
-        };
-        let left_child = build_child(true);
-        let right_child = build_child(false);
-        self.update_bbox(chosen_leaf_i, bounding_box);
-        let node = &mut self.nodes[chosen_leaf_i].node;
-        *node = RTreeNode::Node(vec![left_child, right_child]);
-        Self::update_max_depth(&mut self.max_depth, &self.nodes, chosen_leaf_i, level);
+            {
+                self.nodes[idx].bb = bb;
+            }
+        }
+    }
+
+    /// Frees the internal node `idx` of the given height and collects its children into `orphans`
+    /// together with their height, so that they can be reinserted as whole subtrees.
+    fn collect_orphans(&mut self, idx: usize, height: usize, orphans: &mut Vec<(usize, usize)>) {
+        if let RTreeNode::Node(children) = &mut self.nodes[idx].node {
+            for child in std::mem::take(children) {
+                orphans.push((child, height - 1));
+            }
+        }
+        self.free_node(idx);
+    }
+
+    /// Walks up from `nodes` to the root after removals, dissolving underfull nodes and shrinking bounding boxes,
+    /// then reinserts the orphaned subtrees.
+    ///
+    /// `nodes` must be parents of leaves. They are processed level by level, so that an ancestor shared by many
+    /// of them is visited only once.
+    fn condense_tree(&mut self, mut nodes: Vec<usize>) {
+        let mut orphans = vec![];
+        let mut height = 1;
+        while !nodes.is_empty() {
+            nodes.sort_unstable();
+            nodes.dedup();
+            let mut parents = vec![];
+            for node in nodes {
+                let Some(parent) = self.nodes[node].parent else {
+                    continue;
+                };
+                let size = match &self.nodes[node].node {
+                    RTreeNode::Leaf(_) => 0,
+                    RTreeNode::Node(children) => children.len(),
+                };
+                if size < self.params.min_children {
+                    self.detach_child(parent, node);
+                    self.collect_orphans(node, height, &mut orphans);
+                } else {
+                    self.refit_bb(node);
+                }
+                parents.push(parent);
+            }
+            nodes = parents;
+            height += 1;
+        }
+        self.refit_bb(0);
+
+        // All the children of the root may have been dissolved by a batch removal.
+        if matches!(&self.nodes[0].node, RTreeNode::Node(children) if children.is_empty()) {
+            self.max_depth = 1;
+        }
+        // Taller subtrees first, while the tree is still tall enough to hold them.
+        orphans.sort_unstable_by_key(|(_, height)| *height);
+        while let Some((orphan, height)) = orphans.pop() {
+            if height < self.max_depth {
+                self.insert_subtree(orphan, height, &mut vec![]);
+            } else {
+                // A batch removal may leave the tree shorter than the subtree, so it goes in one level down.
+                self.collect_orphans(orphan, height, &mut orphans);
+            }
+        }
+
+        // If the root is left with a single child node, that child becomes the new root.
+        while let RTreeNode::Node(children) = &self.nodes[0].node {
+            let [child] = children[..] else {
+                break;
+            };
+            if !matches!(self.nodes[child].node, RTreeNode::Node(_)) {
+                break;
+            }
+            let entry = self.free_node(child);
+            if let RTreeNode::Node(grandchildren) = &entry.node {
+                for grandchild in grandchildren {
+                    self.nodes[*grandchild].parent = Some(0);
+                }
+            }
+            self.nodes[0].bb = entry.bb;
+            self.nodes[0].node = entry.node;
+            // Every path from the root goes through the single child, so the tree loses one level.
+            self.max_depth -= 1;
+        }
     }
 
     /// Outputs a dot file for graphviz visualization.
@@ -339,6 +633,10 @@ impl<T: Debug> RTree<T> {
             if vertical { "TB" } else { "LR" }
         )?;
         for (i, node) in self.nodes.iter().enumerate() {
+            // Skip vacant slots in the free list, and the root of an empty tree
+            if node.parent.is_none() && (i != 0 || self.is_empty()) {
+                continue;
+            }
             let color = if matches!(node.node, RTreeNode::Node(_)) {
                 "style=filled fillcolor=\"#ffff7f\""
             } else {
@@ -359,3 +657,393 @@ impl<T: Debug> RTree<T> {
         Ok(())
     }
 }
+
+#[test]
+fn test_remove() {
+    let mut rtree = RTree::new();
+    for i in 0..20 {
+        let pt = sawtooth_point(i);
+        rtree.insert_entry(i, pt.envelope());
+    }
+    for i in (0..20).step_by(3) {
+        let pt = sawtooth_point(i);
+        assert_eq!(rtree.remove(&pt.envelope(), |v| *v == i), Some(i));
+        assert_eq!(rtree.remove(&pt.envelope(), |v| *v == i), None);
+    }
+    let all = BoundingBox::new(-1., -1., 21., 21.);
+    let mut found: Vec<_> = rtree.find_multi(&all).cloned().collect();
+    found.sort();
+    let expected: Vec<_> = (0..20).filter(|i| i % 3 != 0).collect();
+    assert_eq!(found, expected);
+    assert_eq!(rtree.bounding_box().unwrap().min.x(), 1.);
+    assert_eq!(rtree.bounding_box().unwrap().max.x(), 19.);
+
+    // The height shrinks as the root collapses
+    let mut rtree = RTree::with_params(RTreeParams::new(4));
+    for i in 0..200 {
+        let pt = sawtooth_point(i);
+        rtree.insert_entry(i, pt.envelope());
+    }
+    let height = rtree.max_depth();
+    assert!(3 <= height);
+    for i in 0..200 {
+        let pt = sawtooth_point(i);
+        assert_eq!(rtree.remove(&pt.envelope(), |v| *v == i), Some(i));
+        check_invariants(&rtree);
+        assert!(rtree.max_depth() <= height);
+    }
+    assert_eq!(rtree.max_depth(), 1);
+}
+
+#[test]
+fn test_condense_keeps_subtrees() {
+    let mut rtree = RTree::bulk_load_with_params(
+        (0..64).map(|i| (i, sawtooth_point(i).envelope())).collect(),
+        RTreeParams::new(4),
+    );
+    assert_eq!(rtree.max_depth(), 3);
+    let children = |rtree: &RTree<i32>, idx: usize| match &rtree.nodes[idx].node {
+        RTreeNode::Node(children) => children.clone(),
+        RTreeNode::Leaf(_) => vec![],
+    };
+    let values_under = |rtree: &RTree<i32>, idx: usize| {
+        let mut stack = vec![idx];
+        let mut values = vec![];
+        while let Some(node) = stack.pop() {
+            match &rtree.nodes[node].node {
+                RTreeNode::Leaf(value) => values.push(*value),
+                RTreeNode::Node(children) => stack.extend(children),
+            }
+        }
+        values
+    };
+    // Empty all but one child of a node, so that the node is dissolved with a single surviving subtree
+    let dissolved = children(&rtree, 0)[0];
+    let kept = children(&rtree, dissolved)[0];
+    let kept_leaves = children(&rtree, kept);
+    let kept_values = values_under(&rtree, kept);
+    let removed: Vec<_> = values_under(&rtree, dissolved)
+        .into_iter()
+        .filter(|value| !kept_values.contains(value))
+        .collect();
+
+    rtree.retain(|value, _| !removed.contains(value));
+    check_invariants(&rtree);
+    assert_eq!(rtree.len(), 64 - removed.len());
+    // The surviving subtree is reinserted whole, not leaf by leaf
+    assert!(rtree.nodes[kept].parent.is_some());
+    assert_eq!(children(&rtree, kept), kept_leaves);
+}
+
+/// Checks the structural invariants: consistent parent links, tight bounding boxes, node fill within
+/// the parameters and every leaf at the same depth.
+#[cfg(test)]
+fn check_invariants<T: Debug, S: Scalar, const D: usize>(rtree: &RTree<T, S, D>) {
+    let mut leaf_levels = vec![];
+    rtree.walk(&mut |payload| {
+        let RTreeNode::Node(children) = payload.entry.node() else {
+            leaf_levels.push(payload.level);
+            return;
+        };
+        if payload.level != 0 {
+            assert!(rtree.params.min_children <= children.len());
+            assert!(children.len() <= rtree.params.max_children);
+        }
+        for child in children {
+            assert_eq!(rtree.nodes[*child].parent, Some(payload.id));
+        }
+        if let Some(bb) = children
+            .iter()
+            .map(|c| rtree.nodes[*c].bb)
+            .reduce(|a, b| a.get_union(&b))
+        {
+            assert_eq!(bb.min, payload.entry.bb.min);
+            assert_eq!(bb.max, payload.entry.bb.max);
+        }
+    });
+    assert!(leaf_levels.iter().all(|level| *level == rtree.max_depth()));
+}
+
+/// Deterministic points at x = `i` with y stepping through [0, 19], distinct for every `i`.
+#[cfg(test)]
+fn sawtooth_point(i: i32) -> Point {
+    Point::new(i as f64, (i * 7 % 20) as f64)
+}
+
+/// Deterministic points scattered over [0, 100] x [0, 96], distinct for the first 9797 values of `i`.
+#[cfg(test)]
+fn scattered_point(i: i32) -> Point {
+    Point::new((i * 37 % 101) as f64, (i * 53 % 97) as f64)
+}
+
+#[test]
+fn test_balanced() {
+    let mut rtree = RTree::new();
+    for i in 0..100 {
+        // Skewed data that used to deepen a single branch
+        let pt = Point::new(i as f64, 0.);
+        rtree.insert_entry(i, pt.envelope());
+    }
+    check_invariants(&rtree);
+    assert_eq!(
+        rtree
+            .find_multi(&BoundingBox::new(9.5, -1., 19.5, 1.))
+            .count(),
+        10
+    );
+}
+
+#[test]
+fn test_params() {
+    let mut rtree = RTree::with_params(RTreeParams::new(16).min_children(6));
+    for i in 0..1000 {
+        let pt = scattered_point(i);
+        rtree.insert_entry(i, pt.envelope());
+    }
+    check_invariants(&rtree);
+    for i in (0..1000).step_by(2) {
+        let pt = scattered_point(i);
+        assert_eq!(rtree.remove(&pt.envelope(), |v| *v == i), Some(i));
+    }
+    check_invariants(&rtree);
+    assert_eq!(
+        rtree
+            .find_multi(&BoundingBox::new(-1., -1., 102., 102.))
+            .count(),
+        500
+    );
+}
+
+/// Sum of the overlapping areas between sibling nodes, which slows down queries.
+#[cfg(test)]
+fn total_overlap<T: Debug, S: Scalar>(rtree: &RTree<T, S>) -> f64 {
+    let mut overlap = 0.;
+    rtree.walk(&mut |payload| {
+        let RTreeNode::Node(children) = payload.entry.node() else {
+            return;
+        };
+        for (i, a) in children.iter().enumerate() {
+            for b in &children[i + 1..] {
+                if let Some(bb) = rtree.nodes[*a].bb.get_intersection(&rtree.nodes[*b].bb) {
+                    overlap += bb.get_area();
+                }
+            }
+        }
+    });
+    overlap
+}
+
+#[test]
+fn test_rstar() {
+    let build = |params| {
+        let mut rtree = RTree::with_params(params);
+        let mut seed = 12345u32;
+        let mut rand = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 8) as f64 / (1 << 24) as f64
+        };
+        for i in 0..2000 {
+            let center = Point::new(rand() * 1000., rand() * 1000.);
+            let size = Point::new(rand() * 5., rand() * 5.);
+            rtree.insert_entry(i, BoundingBox::from_center_size(center, size));
+        }
+        rtree
+    };
+    let guttman = build(RTreeParams::new(8));
+    let rstar = build(RTreeParams::rstar(8));
+    check_invariants(&rstar);
+    assert_eq!(
+        rstar
+            .find_multi(&BoundingBox::new(-10., -10., 1010., 1010.))
+            .count(),
+        2000
+    );
+    assert!(total_overlap(&rstar) < total_overlap(&guttman));
+}
+
+#[test]
+fn test_empty() {
+    let mut rtree = RTree::new();
+    assert!(rtree.is_empty());
+    assert!(rtree.bounding_box().is_none());
+    let mut count = 0;
+    rtree.walk(&mut |_| count += 1);
+    assert_eq!(count, 0);
+
+    // Data far from the origin does not make the root box span the origin.
+    let pt = Point::new(1000., 2000.);
+    rtree.insert_entry(1, pt.envelope());
+    assert_eq!(rtree.len(), 1);
+    let bb = rtree.bounding_box().unwrap();
+    assert_eq!((bb.min, bb.max), (pt, pt));
+
+    assert_eq!(rtree.remove(&pt.envelope(), |_| true), Some(1));
+    assert!(rtree.is_empty());
+    assert!(rtree.bounding_box().is_none());
+
+    let pt = Point::new(-500., -500.);
+    rtree.insert_entry(2, pt.envelope());
+    let bb = rtree.bounding_box().unwrap();
+    assert_eq!((bb.min, bb.max), (pt, pt));
+}
+
+#[test]
+fn test_scalar_types() {
+    let mut rtree: RTree<i32, i32> = RTree::new();
+    for i in 0..50 {
+        let pt = Point::new(i * 37 % 101, i * 53 % 97);
+        rtree.insert_entry(i, pt.envelope());
+    }
+    check_invariants(&rtree);
+    // Integer boxes touching at the edge are found exactly
+    let pt = Point::new(37, 53);
+    assert_eq!(rtree.find(&BoundingBox::new(30, 40, 37, 53)), Some(&1));
+    assert_eq!(rtree.remove(&pt.envelope(), |_| true), Some(1));
+    assert_eq!(rtree.find(&BoundingBox::new(30, 40, 37, 53)), None);
+
+    let mut rtree: RTree<i32, f32> = RTree::with_params(RTreeParams::rstar(6));
+    for i in 0..50 {
+        let pt = Point::new((i * 37 % 101) as f32, (i * 53 % 97) as f32);
+        rtree.insert_entry(i, BoundingBox::from_center_size(pt, Point::new(0.5, 0.5)));
+    }
+    check_invariants(&rtree);
+    assert_eq!(
+        rtree
+            .find_multi(&BoundingBox::new(-1., -1., 102., 102.))
+            .count(),
+        50
+    );
+}
+
+#[test]
+fn test_3d() {
+    // Spatio-temporal entries: x, y and time.
+    let pt = |i: i32| {
+        let p = scattered_point(i);
+        Point::from([p.x(), p.y(), i as f64])
+    };
+    let size = Point::from([0.5, 0.5, 0.5]);
+    for params in [RTreeParams::new(6), RTreeParams::rstar(6)] {
+        let mut rtree: RTree<i32, f64, 3> = RTree::with_params(params);
+        for i in 0..100 {
+            rtree.insert_entry(i, BoundingBox::from_center_size(pt(i), size));
+        }
+        check_invariants(&rtree);
+        assert_eq!(rtree.len(), 100);
+        // The same place at another time is not found.
+        let at = |i: i32, t: f64| {
+            let mut p = pt(i);
+            p.coords[2] = t;
+            p.envelope()
+        };
+        assert_eq!(rtree.find(&at(10, 10.)), Some(&10));
+        assert_eq!(rtree.find(&at(10, 50.)), None);
+        let early = BoundingBox::from_minmax(
+            Point::from([-1., -1., -1.]),
+            Point::from([102., 102., 19.4]),
+        );
+        assert_eq!(rtree.find_multi(&early).count(), 20);
+        assert_eq!(rtree.remove(&at(10, 10.), |_| true), Some(10));
+        assert_eq!(rtree.find_multi(&early).count(), 19);
+        check_invariants(&rtree);
+    }
+
+    let entries = (0..200)
+        .map(|i| (i, BoundingBox::from_center_size(pt(i), size)))
+        .collect::<Vec<_>>();
+    for rtree in [
+        RTree::bulk_load(entries.clone()),
+        RTree::bulk_load_hilbert(entries),
+    ] {
+        check_invariants(&rtree);
+        let bb = rtree.bounding_box().unwrap();
+        assert_eq!(bb.max.z(), 199.5);
+        let mut found: Vec<_> = rtree
+            .find_multi(&BoundingBox::from_minmax(
+                Point::from([-1., -1., 99.6]),
+                Point::from([102., 102., 109.4]),
+            ))
+            .copied()
+            .collect();
+        found.sort();
+        assert_eq!(found, (100..110).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_insert_object() {
+    let mut rtree = RTree::new();
+    for i in 0..20 {
+        rtree.insert(sawtooth_point(i));
+    }
+    check_invariants(&rtree);
+    assert_eq!(
+        rtree.find(&BoundingBox::new(2.5, 0., 3.5, 20.)),
+        Some(&Point::new(3., 1.))
+    );
+    let bb = rtree.bounding_box().unwrap();
+    assert_eq!((bb.min, bb.max), (Point::new(0., 0.), Point::new(19., 19.)));
+
+    let mut rtree: RTree<BoundingBox<i32>, i32> = RTree::new();
+    let id = rtree.insert(BoundingBox::new(1, 2, 3, 4));
+    let (stored, value) = (rtree.bounding_box_of(id).unwrap(), rtree.get(id).unwrap());
+    assert_eq!((stored.min, stored.max), (value.min, value.max));
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Circle {
+    center: Point,
+    radius: f64,
+}
+
+#[cfg(test)]
+impl Circle {
+    /// Circles of radius 1 to 4 at [`scattered_point`]s.
+    fn scattered(i: i32) -> Self {
+        Self {
+            center: scattered_point(i),
+            radius: (i % 4 + 1) as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+impl RTreeObject for Circle {
+    fn envelope(&self) -> BoundingBox {
+        BoundingBox::from_center_size(self.center, Point::new(self.radius, self.radius))
+    }
+}
+
+#[cfg(test)]
+impl crate::object::ExactGeometry for Circle {
+    fn intersects_box(&self, bounding_box: &BoundingBox) -> bool {
+        bounding_box.distance2_to_point(&self.center) <= self.radius * self.radius
+    }
+
+    fn contains_point(&self, point: &Point) -> bool {
+        self.distance_to_point(point) == 0.
+    }
+
+    fn distance_to_point(&self, point: &Point) -> f64 {
+        let d = *point - self.center;
+        ((d.x() * d.x() + d.y() * d.y()).sqrt() - self.radius).max(0.)
+    }
+}
+
+#[cfg(test)]
+impl crate::object::RayIntersection for Circle {
+    fn intersect_ray(&self, origin: &Point, direction: &Point, max_t: f64) -> Option<f64> {
+        // Solve |origin + t * direction - center| = radius
+        let oc = *origin - self.center;
+        let a = direction.x() * direction.x() + direction.y() * direction.y();
+        let b = oc.x() * direction.x() + oc.y() * direction.y();
+        let c = oc.x() * oc.x() + oc.y() * oc.y() - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if a == 0. || discriminant < 0. {
+            return None;
+        }
+        let t = ((-b - discriminant.sqrt()) / a).max(0.);
+        (t <= max_t && -b + discriminant.sqrt() >= 0.).then_some(t)
+    }
+}
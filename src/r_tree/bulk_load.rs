@@ -0,0 +1,386 @@
+use super::{RTree, RTreeEntry, RTreeNode, RTreeParams};
+use crate::{bounding_box::BoundingBox, scalar::Scalar};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, scattered_point};
+#[cfg(test)]
+use crate::{object::RTreeObject, point::Point};
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Builds a packed RTree from all the entries at once with Sort-Tile-Recursive (STR) algorithm.
+    ///
+    /// It is much faster than inserting entries one by one, and the nodes come out nearly 100% full.
+    /// Like [`RTree::insert_entry`], it is the caller's responsibility that each bounding box bounds its value.
+    pub fn bulk_load(entries: Vec<(T, BoundingBox<S, D>)>) -> Self {
+        Self::bulk_load_with_params(entries, RTreeParams::default())
+    }
+
+    /// [`RTree::bulk_load`] with the given parameters.
+    pub fn bulk_load_with_params(
+        entries: Vec<(T, BoundingBox<S, D>)>,
+        params: RTreeParams<D>,
+    ) -> Self {
+        let mut rtree = Self::with_params(params);
+        rtree.pack(entries, |rtree, ids| rtree.str_groups(ids));
+        rtree
+    }
+
+    /// Builds a packed static RTree by sorting the entries along the Hilbert curve through the centers of
+    /// their bounding boxes, then filling the nodes in that order, level by level.
+    ///
+    /// It is even faster to build than [`RTree::bulk_load`] and gives very little overlap between nodes,
+    /// which suits data that rarely changes.
+    pub fn bulk_load_hilbert(entries: Vec<(T, BoundingBox<S, D>)>) -> Self {
+        Self::bulk_load_hilbert_with_params(entries, RTreeParams::default())
+    }
+
+    /// [`RTree::bulk_load_hilbert`] with the given parameters.
+    pub fn bulk_load_hilbert_with_params(
+        entries: Vec<(T, BoundingBox<S, D>)>,
+        params: RTreeParams<D>,
+    ) -> Self {
+        let mut rtree = Self::with_params(params);
+        rtree.pack(entries, |rtree, ids| rtree.hilbert_groups(ids));
+        rtree
+    }
+
+    /// Builds the tree bottom-up into this empty RTree. `group` decides which nodes of a level share a parent.
+    fn pack(
+        &mut self,
+        entries: Vec<(T, BoundingBox<S, D>)>,
+        group: impl Fn(&Self, Vec<usize>) -> Vec<Vec<usize>>,
+    ) {
+        let leaves = entries
+            .into_iter()
+            .map(|(value, bb)| self.append_leaf(value, bb).0)
+            .collect();
+        self.pack_leaves(leaves, group);
+    }
+
+    /// [`RTree::pack`] with leaf nodes that are already in the arena.
+    pub(super) fn pack_leaves(
+        &mut self,
+        leaves: Vec<usize>,
+        group: impl Fn(&Self, Vec<usize>) -> Vec<Vec<usize>>,
+    ) {
+        self.len = leaves.len();
+        let mut level = leaves;
+        let mut height = 1;
+        while self.params.max_children < level.len() {
+            level = group(self, level)
+                .into_iter()
+                .map(|children| {
+                    let node = self.append_entry(RTreeEntry {
+                        bb: self.nodes[children[0]].bb,
+                        parent: None,
+                        id: None,
+                        node: RTreeNode::Node(vec![]),
+                    });
+                    self.set_children(node, children);
+                    node
+                })
+                .collect();
+            height += 1;
+        }
+        self.set_children(0, level);
+        self.max_depth = height;
+    }
+
+    fn set_children(&mut self, node: usize, children: Vec<usize>) {
+        for child in &children {
+            self.nodes[*child].parent = Some(node);
+        }
+        self.nodes[node].node = RTreeNode::Node(children);
+        self.refit_bb(node);
+    }
+
+    /// Sort-Tile-Recursive grouping. Sorts the nodes by the first coordinates of their centers into slabs,
+    /// then each slab by the next coordinate in turn, and finally cuts the slabs along the last axis
+    /// into groups of at most `max_children`.
+    pub(super) fn str_groups(&self, mut ids: Vec<usize>) -> Vec<Vec<usize>> {
+        let mut groups = vec![];
+        self.str_slice(&mut ids, 0, &mut groups);
+        groups
+    }
+
+    fn str_slice(&self, ids: &mut [usize], axis: usize, groups: &mut Vec<Vec<usize>>) {
+        let max = self.params.max_children;
+        let min = self.params.min_children;
+        let center = |id: &usize| self.nodes[*id].bb.to_f64().get_center().coords[axis];
+        ids.sort_by(|a, b| center(a).total_cmp(&center(b)));
+        if D <= axis + 1 {
+            groups.extend(split_into_chunks(ids, max, min));
+            return;
+        }
+
+        // With P nodes to make in k remaining dimensions, there are P^(1/k) slabs along this axis,
+        // each of which holds P^((k-1)/k) nodes.
+        let dims = (D - axis) as i32;
+        let num_nodes = ids.len().div_ceil(max);
+        let num_slices = (num_nodes as f64).powf(1. / dims as f64).ceil() as usize;
+        let slice_len = num_slices.pow(dims as u32 - 1) * max;
+        let mut rest = ids;
+        for slice_size in chunk_sizes(rest.len(), slice_len, min) {
+            let (slice, next) = rest.split_at_mut(slice_size);
+            self.str_slice(slice, axis + 1, groups);
+            rest = next;
+        }
+    }
+
+    /// Sorts the nodes by the Hilbert index of their centers and cuts them into groups of at most `max_children`.
+    fn hilbert_groups(&self, ids: Vec<usize>) -> Vec<Vec<usize>> {
+        let ids = self.hilbert_sort(ids);
+        split_into_chunks(&ids, self.params.max_children, self.params.min_children)
+    }
+
+    /// Sorts the nodes by the Hilbert index of their centers, so that nearby nodes come close in the order.
+    fn hilbert_sort(&self, ids: Vec<usize>) -> Vec<usize> {
+        let Some(bounds) = ids
+            .iter()
+            .map(|id| self.nodes[*id].bb.to_f64())
+            .reduce(|a, b| a.get_union(&b))
+        else {
+            return vec![];
+        };
+        // Map the centers onto a grid of 2^order cells per axis, so that the index fits in 64 bits.
+        let order = (64 / D.max(1) as u32).clamp(1, 16);
+        let cells = ((1u32 << order) - 1) as f64;
+        let to_cell = |v: f64, min: f64, max: f64| {
+            if min < max {
+                ((v - min) / (max - min) * cells) as u32
+            } else {
+                0
+            }
+        };
+        let mut keyed: Vec<_> = ids
+            .into_iter()
+            .map(|id| {
+                let center = self.nodes[id].bb.to_f64().get_center();
+                let cell: [u32; D] = std::array::from_fn(|axis| {
+                    to_cell(
+                        center.coords[axis],
+                        bounds.min.coords[axis],
+                        bounds.max.coords[axis],
+                    )
+                });
+                (hilbert_index(order, cell), id)
+            })
+            .collect();
+        keyed.sort_by_key(|(key, _)| *key);
+        keyed.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Inserts many entries into a non-empty tree.
+    ///
+    /// The entries are sorted along the Hilbert curve first, so that consecutive entries tend to go down the same
+    /// path. Instead of recomputing the bounding boxes of the ancestors from their children after each insertion,
+    /// they are only enlarged, stopping at the first one that already contains the new entry.
+    fn insert_batch(&mut self, entries: Vec<(T, BoundingBox<S, D>)>) {
+        self.len += entries.len();
+        let leaves: Vec<_> = entries
+            .into_iter()
+            .map(|(value, bb)| self.append_leaf(value, bb).0)
+            .collect();
+        for leaf in self.hilbert_sort(leaves) {
+            let bb = self.nodes[leaf].bb;
+            let parent = self.choose_subtree(&bb, 1);
+            let mut node = self.add_child(parent, leaf, 1, &mut vec![]);
+            while let Some(n) = node {
+                if self.nodes[n].bb.contains(&bb) {
+                    break;
+                }
+                self.nodes[n].bb = self.nodes[n].bb.get_union(&bb);
+                node = self.nodes[n].parent;
+            }
+        }
+    }
+}
+
+/// Collects the entries with [`RTree::bulk_load`].
+impl<T: Debug, S: Scalar, const D: usize> FromIterator<(T, BoundingBox<S, D>)> for RTree<T, S, D> {
+    fn from_iter<I: IntoIterator<Item = (T, BoundingBox<S, D>)>>(iter: I) -> Self {
+        Self::bulk_load(iter.into_iter().collect())
+    }
+}
+
+/// Bulk loads the entries like [`RTree::bulk_load`] if the tree is empty. Otherwise they are buffered and inserted
+/// in a batch, which updates fewer bounding boxes than calling [`RTree::insert_entry`] for each of them.
+impl<T: Debug, S: Scalar, const D: usize> Extend<(T, BoundingBox<S, D>)> for RTree<T, S, D> {
+    fn extend<I: IntoIterator<Item = (T, BoundingBox<S, D>)>>(&mut self, iter: I) {
+        let entries: Vec<_> = iter.into_iter().collect();
+        if self.is_empty() {
+            self.pack(entries, |rtree, ids| rtree.str_groups(ids));
+        } else {
+            self.insert_batch(entries);
+        }
+    }
+}
+
+/// Distance along the `D`-dimensional Hilbert curve of the given order to the cell.
+///
+/// It uses Skilling's algorithm ("Programming the Hilbert curve", 2004), which transforms the coordinates
+/// in place into the "transposed" index, whose bits are then interleaved. `order * D` must be at most 64.
+fn hilbert_index<const D: usize>(order: u32, mut x: [u32; D]) -> u64 {
+    let m = 1u32 << (order - 1);
+    // Inverse undo excess work
+    let mut q = m;
+    while 1 < q {
+        let p = q - 1;
+        for i in 0..D {
+            if x[i] & q != 0 {
+                x[0] ^= p;
+            } else {
+                let t = (x[0] ^ x[i]) & p;
+                x[0] ^= t;
+                x[i] ^= t;
+            }
+        }
+        q >>= 1;
+    }
+    // Gray encode
+    for i in 1..D {
+        x[i] ^= x[i - 1];
+    }
+    let mut t = 0;
+    let mut q = m;
+    while 1 < q {
+        if x[D - 1] & q != 0 {
+            t ^= q - 1;
+        }
+        q >>= 1;
+    }
+    for v in &mut x {
+        *v ^= t;
+    }
+
+    let mut d = 0u64;
+    for bit in (0..order).rev() {
+        for v in &x {
+            d = (d << 1) | ((v >> bit) & 1) as u64;
+        }
+    }
+    d
+}
+
+/// Splits `ids` into consecutive groups of `max` elements, except that the last two share their elements
+/// evenly if the last one would have less than `min`.
+fn split_into_chunks(ids: &[usize], max: usize, min: usize) -> Vec<Vec<usize>> {
+    let mut rest = ids;
+    chunk_sizes(ids.len(), max, min)
+        .into_iter()
+        .map(|size| {
+            let (chunk, next) = rest.split_at(size);
+            rest = next;
+            chunk.to_vec()
+        })
+        .collect()
+}
+
+fn chunk_sizes(len: usize, max: usize, min: usize) -> Vec<usize> {
+    let mut sizes = vec![max; len / max];
+    let rest = len % max;
+    if 0 < rest {
+        sizes.push(rest);
+    }
+    if let [.., a, b] = sizes[..] {
+        if b < min {
+            let n = sizes.len();
+            sizes[n - 2] = (a + b) - (a + b) / 2;
+            sizes[n - 1] = (a + b) / 2;
+        }
+    }
+    sizes
+}
+
+#[test]
+fn test_hilbert_index() {
+    // The curve of order 1 visits (0, 0), (0, 1), (1, 1), (1, 0) in this order.
+    assert_eq!(hilbert_index(1, [0, 0]), 0);
+    assert_eq!(hilbert_index(1, [0, 1]), 1);
+    assert_eq!(hilbert_index(1, [1, 1]), 2);
+    assert_eq!(hilbert_index(1, [1, 0]), 3);
+    // Consecutive indices are adjacent cells, and every cell is visited once.
+    let mut cells: Vec<_> = (0..8)
+        .flat_map(|x| (0..8).map(move |y| (hilbert_index(3, [x, y]), [x, y])))
+        .collect();
+    cells.sort();
+    for (i, w) in cells.windows(2).enumerate() {
+        assert_eq!(w[0].0, i as u64);
+        assert_eq!(
+            w[0].1[0].abs_diff(w[1].1[0]) + w[0].1[1].abs_diff(w[1].1[1]),
+            1
+        );
+    }
+    let mut cells: Vec<_> = (0..4)
+        .flat_map(|x| {
+            (0..4).flat_map(move |y| (0..4).map(move |z| (hilbert_index(2, [x, y, z]), [x, y, z])))
+        })
+        .collect();
+    cells.sort();
+    for (i, w) in cells.windows(2).enumerate() {
+        assert_eq!(w[0].0, i as u64);
+        let dist: u32 = (0..3).map(|axis| w[0].1[axis].abs_diff(w[1].1[axis])).sum();
+        assert_eq!(dist, 1);
+    }
+}
+
+#[test]
+fn test_bulk_load() {
+    for n in [0, 3, 4, 5, 17, 100, 1234] {
+        let entries: Vec<_> = (0..n)
+            .map(|i| {
+                let pt = scattered_point(i);
+                (i, pt.envelope())
+            })
+            .collect();
+        let hilbert_tree =
+            RTree::bulk_load_hilbert_with_params(entries.clone(), RTreeParams::new(6));
+        let str_tree = RTree::bulk_load_with_params(entries, RTreeParams::new(6));
+        for rtree in [str_tree, hilbert_tree] {
+            check_invariants(&rtree);
+            let mut found: Vec<_> = rtree
+                .find_multi(&BoundingBox::new(-1., -1., 102., 102.))
+                .cloned()
+                .collect();
+            found.sort();
+            assert_eq!(found, (0..n).collect::<Vec<_>>());
+        }
+    }
+}
+
+#[test]
+fn test_extend() {
+    let entry = |i: i32| {
+        (
+            i,
+            BoundingBox::from_center_size(scattered_point(i), Point::new(0.5, 0.5)),
+        )
+    };
+    let all = BoundingBox::new(-1., -1., 102., 102.);
+
+    let rtree: RTree<_> = (0..100).map(entry).collect();
+    check_invariants(&rtree);
+    assert_eq!(rtree.len(), 100);
+
+    for params in [RTreeParams::new(4), RTreeParams::rstar(6)] {
+        let mut rtree = RTree::with_params(params);
+        rtree.extend((0..100).map(entry));
+        check_invariants(&rtree);
+        rtree.extend((100..300).map(entry));
+        check_invariants(&rtree);
+        rtree.extend((300..301).map(entry));
+        check_invariants(&rtree);
+        assert_eq!(rtree.len(), 301);
+        let mut found: Vec<_> = rtree.find_multi(&all).copied().collect();
+        found.sort();
+        assert_eq!(found, (0..301).collect::<Vec<_>>());
+        assert_eq!(rtree.find(&scattered_point(250).envelope()), Some(&250));
+
+        // An emptied tree is bulk loaded again.
+        rtree.retain(|_, _| false);
+        rtree.extend((0..50).map(entry));
+        check_invariants(&rtree);
+        assert_eq!(rtree.find_multi(&all).count(), 50);
+    }
+}
@@ -0,0 +1,100 @@
+use super::{RTree, RTreeNode};
+use crate::{bounding_box::BoundingBox, scalar::Scalar};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, scattered_point, RTreeParams};
+#[cfg(test)]
+use crate::object::RTreeObject;
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Keeps only the entries for which `f` returns true, and removes the rest.
+    ///
+    /// It visits every entry in a single pass over the nodes, and fixes the structure of the tree once
+    /// for all the removed entries, which is much faster than calling [`RTree::remove`] for each of them.
+    pub fn retain(&mut self, mut f: impl FnMut(&T, &BoundingBox<S, D>) -> bool) {
+        let leaves: Vec<_> = (0..self.nodes.len())
+            .filter(|i| match self.nodes[*i].node {
+                RTreeNode::Leaf(ref value) => !f(value, &self.nodes[*i].bb),
+                RTreeNode::Node(_) => false,
+            })
+            .collect();
+        self.remove_leaves(leaves);
+    }
+
+    /// Removes all the entries that intersect with the given bounding box and returns them,
+    /// in no particular order.
+    ///
+    /// Like [`RTree::retain`], the structure of the tree is fixed once for all of them.
+    pub fn drain_in(&mut self, bounding_box: &BoundingBox<S, D>) -> impl Iterator<Item = T> {
+        let leaves = self.find_leaves(bounding_box);
+        self.remove_leaves(leaves)
+            .into_iter()
+            .map(|(value, _)| value)
+    }
+
+    /// Detaches and frees the given leaf nodes, then condenses the tree from all their parents at once.
+    /// Returns the values with their stored bounding boxes.
+    pub(super) fn remove_leaves(&mut self, leaves: Vec<usize>) -> Vec<(T, BoundingBox<S, D>)> {
+        let mut parents = Vec::with_capacity(leaves.len());
+        let mut values = Vec::with_capacity(leaves.len());
+        for leaf in leaves {
+            let Some(parent) = self.nodes[leaf].parent else {
+                continue;
+            };
+            self.detach_child(parent, leaf);
+            let entry = self.free_node(leaf);
+            if let Some(id) = entry.id {
+                self.release_id(id);
+            }
+            if let RTreeNode::Leaf(value) = entry.node {
+                values.push((value, entry.bb));
+            }
+            parents.push(parent);
+        }
+        self.len -= values.len();
+        if !parents.is_empty() {
+            self.condense_tree(parents);
+        }
+        values
+    }
+}
+
+#[test]
+fn test_retain() {
+    for params in [RTreeParams::new(4), RTreeParams::rstar(6)] {
+        let mut rtree = RTree::with_params(params);
+        let ids: Vec<_> = (0..300)
+            .map(|i| rtree.insert_entry(i, scattered_point(i).envelope()))
+            .collect();
+
+        rtree.retain(|value, bb| {
+            assert_eq!(bb.min, scattered_point(*value));
+            value % 3 != 0
+        });
+        check_invariants(&rtree);
+        assert_eq!(rtree.len(), 200);
+        assert!(!rtree.contains(ids[0]));
+        assert_eq!(rtree.get(ids[1]), Some(&1));
+
+        let region = BoundingBox::new(0., 0., 50., 50.);
+        let expected: Vec<_> = (0..300)
+            .filter(|i| i % 3 != 0 && region.intersects(&scattered_point(*i).envelope()))
+            .collect();
+        let mut drained: Vec<_> = rtree.drain_in(&region).collect();
+        drained.sort();
+        assert_eq!(drained, expected);
+        check_invariants(&rtree);
+        assert_eq!(rtree.len(), 200 - expected.len());
+        assert_eq!(rtree.find(&region), None);
+
+        // Removing everything leaves an empty tree that still works.
+        rtree.retain(|_, _| false);
+        check_invariants(&rtree);
+        assert!(rtree.is_empty());
+        assert_eq!(rtree.max_depth(), 1);
+        rtree.insert_entry(1000, scattered_point(0).envelope());
+        assert_eq!(rtree.find(&region), Some(&1000));
+        check_invariants(&rtree);
+    }
+}
@@ -0,0 +1,103 @@
+use super::{RTree, RTreeNode};
+use crate::scalar::Scalar;
+use std::{collections::VecDeque, fmt::Debug};
+
+#[cfg(test)]
+use super::{check_invariants, sawtooth_point};
+#[cfg(test)]
+use crate::object::RTreeObject;
+
+/// The order of the nodes in the arena after [`RTree::compact`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompactOrder {
+    /// Each node is followed by its subtree, so a query that descends into a subtree reads nearby memory.
+    #[default]
+    DepthFirst,
+    /// Nodes are laid out level by level, so siblings are adjacent.
+    BreadthFirst,
+}
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Rewrites the node arena in the given traversal order, dropping the vacant slots left by removals.
+    ///
+    /// Node indexes, such as the ones in [`WalkCallbackPayload`](super::WalkCallbackPayload), change,
+    /// but [`EntryId`](super::EntryId)s stay valid.
+    pub fn compact(&mut self, order: CompactOrder) {
+        let mut traversal = Vec::with_capacity(self.nodes.len() - self.free_nodes.len());
+        match order {
+            CompactOrder::DepthFirst => {
+                let mut stack = vec![0];
+                while let Some(node) = stack.pop() {
+                    traversal.push(node);
+                    if let RTreeNode::Node(children) = &self.nodes[node].node {
+                        stack.extend(children.iter().rev());
+                    }
+                }
+            }
+            CompactOrder::BreadthFirst => {
+                let mut queue = VecDeque::from([0]);
+                while let Some(node) = queue.pop_front() {
+                    traversal.push(node);
+                    if let RTreeNode::Node(children) = &self.nodes[node].node {
+                        queue.extend(children.iter());
+                    }
+                }
+            }
+        }
+
+        let mut new_index = vec![usize::MAX; self.nodes.len()];
+        for (new, old) in traversal.iter().enumerate() {
+            new_index[*old] = new;
+        }
+        let mut old_nodes: Vec<_> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut nodes = Vec::with_capacity(traversal.len());
+        for (new, old) in traversal.into_iter().enumerate() {
+            let mut entry = old_nodes[old].take().unwrap();
+            entry.parent = entry.parent.map(|parent| new_index[parent]);
+            if let RTreeNode::Node(children) = &mut entry.node {
+                for child in children {
+                    *child = new_index[*child];
+                }
+            }
+            if let Some(id) = entry.id {
+                self.relocate_id(id, new);
+            }
+            nodes.push(entry);
+        }
+        self.nodes = nodes;
+        self.free_nodes.clear();
+    }
+}
+
+#[test]
+fn test_compact() {
+    let mut rtree = RTree::new();
+    let bb = |i: i32| sawtooth_point(i).envelope();
+    let mut ids: Vec<_> = (0..20).map(|i| rtree.insert_entry(i, bb(i))).collect();
+    // Churn does not grow the arena thanks to the free list
+    for _ in 0..10 {
+        for i in 0..20 {
+            assert_eq!(rtree.remove(&bb(i), |v| *v == i), Some(i));
+            ids[i as usize] = rtree.insert_entry(i, bb(i));
+        }
+    }
+    assert!(rtree.nodes.len() < 60);
+
+    for i in 0..15 {
+        assert_eq!(rtree.remove(&bb(i), |v| *v == i), Some(i));
+    }
+    for order in [CompactOrder::DepthFirst, CompactOrder::BreadthFirst] {
+        rtree.compact(order);
+        check_invariants(&rtree);
+        assert!(rtree.free_nodes.is_empty());
+        let mut count = 0;
+        rtree.walk(&mut |_| count += 1);
+        assert_eq!(rtree.nodes.len(), count);
+        for i in 15..20 {
+            assert_eq!(rtree.get(ids[i as usize]), Some(&i));
+        }
+    }
+}
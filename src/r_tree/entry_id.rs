@@ -0,0 +1,260 @@
+use super::{RTree, RTreeEntry, RTreeNode};
+use crate::{bounding_box::BoundingBox, scalar::Scalar};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, sawtooth_point, RTreeParams};
+#[cfg(test)]
+use crate::{object::RTreeObject, point::Point};
+
+/// A stable handle to an entry in an [`RTree`], returned by [`RTree::insert_entry`].
+///
+/// Unlike the node ids in [`WalkCallbackPayload`](super::WalkCallbackPayload), it keeps pointing to the same entry
+/// while the tree is restructured. It is generational, so an id of a removed entry never aliases an entry inserted later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntryId {
+    index: u32,
+    generation: u32,
+}
+
+/// A slot in the id table. `node` is the index of the leaf node while the entry is alive.
+#[derive(Debug)]
+pub(super) struct IdSlot {
+    generation: u32,
+    node: Option<usize>,
+}
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Appends a leaf node with a newly allocated id, without attaching it to the tree.
+    /// The bounding box is enlarged by the fat margin.
+    pub(super) fn append_leaf(&mut self, value: T, bb: BoundingBox<S, D>) -> (usize, EntryId) {
+        let bb = self.fatten(&bb);
+        self.append_fat_leaf(value, bb)
+    }
+
+    /// Same as [`RTree::append_leaf`], but for a bounding box that already has the fat margin,
+    /// e.g. one moved from another tree.
+    pub(super) fn append_fat_leaf(&mut self, value: T, bb: BoundingBox<S, D>) -> (usize, EntryId) {
+        let idx = self.append_entry(RTreeEntry {
+            bb,
+            parent: None,
+            id: None,
+            node: RTreeNode::Leaf(value),
+        });
+        (idx, self.allocate_id(idx))
+    }
+
+    /// Allocates a new id for the leaf node at `idx`.
+    pub(super) fn allocate_id(&mut self, idx: usize) -> EntryId {
+        let id = if let Some(index) = self.free_ids.pop() {
+            let slot = &mut self.ids[index as usize];
+            slot.node = Some(idx);
+            EntryId {
+                index,
+                generation: slot.generation,
+            }
+        } else {
+            self.ids.push(IdSlot {
+                generation: 0,
+                node: Some(idx),
+            });
+            EntryId {
+                index: (self.ids.len() - 1) as u32,
+                generation: 0,
+            }
+        };
+        self.nodes[idx].id = Some(id);
+        id
+    }
+
+    /// Invalidates the id of a removed entry. The generation is bumped so that the old id never matches again.
+    pub(super) fn release_id(&mut self, id: EntryId) {
+        let slot = &mut self.ids[id.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.node = None;
+        self.free_ids.push(id.index);
+    }
+
+    /// Records that the leaf node of the entry has moved to another slot.
+    pub(super) fn relocate_id(&mut self, id: EntryId, node: usize) {
+        self.ids[id.index as usize].node = Some(node);
+    }
+
+    /// Returns the index of the leaf node of the entry, if it is still in the tree.
+    pub(super) fn leaf_of(&self, id: EntryId) -> Option<usize> {
+        let slot = self.ids.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node
+    }
+
+    pub fn contains(&self, id: EntryId) -> bool {
+        self.leaf_of(id).is_some()
+    }
+
+    pub fn get(&self, id: EntryId) -> Option<&T> {
+        match self.nodes[self.leaf_of(id)?].node {
+            RTreeNode::Leaf(ref value) => Some(value),
+            RTreeNode::Node(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the entry. The bounding box stays the same, so the caller should not change
+    /// the value in a way that moves it out of the bounding box.
+    pub fn get_mut(&mut self, id: EntryId) -> Option<&mut T> {
+        let leaf = self.leaf_of(id)?;
+        match self.nodes[leaf].node {
+            RTreeNode::Leaf(ref mut value) => Some(value),
+            RTreeNode::Node(_) => None,
+        }
+    }
+
+    /// Returns the bounding box of the entry as stored in the tree, which includes the fat margin if it is set.
+    pub fn bounding_box_of(&self, id: EntryId) -> Option<&BoundingBox<S, D>> {
+        Some(&self.nodes[self.leaf_of(id)?].bb)
+    }
+
+    fn fatten(&self, bb: &BoundingBox<S, D>) -> BoundingBox<S, D> {
+        if 0. < self.params.fat_margin {
+            bb.get_expanded(S::from_f64(self.params.fat_margin))
+        } else {
+            *bb
+        }
+    }
+
+    /// Updates the bounding box of an entry, e.g. when the object moved. Returns false if the id is not in the tree.
+    ///
+    /// If the new box is still inside the stored box with the fat margin, nothing happens. If it still fits in the
+    /// parent node, only the bounding boxes are updated. Otherwise the entry is removed and reinserted.
+    /// The id stays valid in any case.
+    pub fn update_bbox(&mut self, id: EntryId, bounding_box: BoundingBox<S, D>) -> bool {
+        let Some(leaf) = self.leaf_of(id) else {
+            return false;
+        };
+        if 0. < self.params.fat_margin && self.nodes[leaf].bb.contains(&bounding_box) {
+            return true;
+        }
+        let bb = self.fatten(&bounding_box);
+        self.nodes[leaf].bb = bb;
+        let Some(parent) = self.nodes[leaf].parent else {
+            return true;
+        };
+        if self.nodes[parent].bb.contains(&bb) {
+            self.refit_upward(parent);
+        } else {
+            self.detach_child(parent, leaf);
+            self.condense_tree(vec![parent]);
+            self.insert_node(leaf);
+        }
+        true
+    }
+}
+
+#[test]
+fn test_entry_id() {
+    let mut rtree = RTree::new();
+    let ids: Vec<_> = (0..20)
+        .map(|i| rtree.insert_entry(i, sawtooth_point(i).envelope()))
+        .collect();
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(rtree.get(*id), Some(&(i as i32)));
+        assert_eq!(
+            rtree.bounding_box_of(*id).unwrap().min,
+            sawtooth_point(i as i32)
+        );
+    }
+    *rtree.get_mut(ids[3]).unwrap() = 100;
+    assert_eq!(rtree.get(ids[3]), Some(&100));
+
+    let bb = sawtooth_point(5).envelope();
+    assert_eq!(rtree.remove(&bb, |v| *v == 5), Some(5));
+    assert!(!rtree.contains(ids[5]));
+    assert_eq!(rtree.get(ids[5]), None);
+    // The slot is reused by a new entry, but the old id does not alias it.
+    let new_id = rtree.insert_entry(5, bb);
+    assert_ne!(new_id, ids[5]);
+    assert_eq!(rtree.get(ids[5]), None);
+    assert_eq!(rtree.get(new_id), Some(&5));
+    // Ids survive restructuring by removals.
+    for i in 10..20 {
+        let bb = sawtooth_point(i).envelope();
+        assert_eq!(rtree.remove(&bb, |v| *v == i), Some(i));
+    }
+    for (i, id) in ids
+        .iter()
+        .enumerate()
+        .take(10)
+        .filter(|(i, _)| ![3, 5].contains(i))
+    {
+        assert_eq!(rtree.get(*id), Some(&(i as i32)));
+    }
+}
+
+#[test]
+fn test_update_bbox() {
+    for fat_margin in [0., 0.5] {
+        let mut rtree = RTree::with_params(RTreeParams::new(4).fat_margin(fat_margin));
+        let ids: Vec<_> = (0..20)
+            .map(|i| rtree.insert_entry(i, sawtooth_point(i).envelope()))
+            .collect();
+        // Small moves, then large moves
+        for (offset, i) in [(0.1, 2), (0.2, 2), (50., 3), (50., 7), (-50., 12)] {
+            let moved = sawtooth_point(i) + Point::new(offset, offset);
+            assert!(rtree.update_bbox(ids[i as usize], moved.envelope()));
+            assert!(rtree
+                .bounding_box_of(ids[i as usize])
+                .unwrap()
+                .contains(&moved.envelope()));
+            let found: Vec<_> = rtree
+                .find_multi(&BoundingBox::from_center_size(
+                    moved,
+                    Point::new(0.01, 0.01),
+                ))
+                .collect();
+            assert!(found.contains(&&i));
+            check_invariants(&rtree);
+        }
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(rtree.get(*id), Some(&(i as i32)));
+        }
+    }
+}
+
+#[test]
+fn test_update_bbox_is_local() {
+    // Two clusters far apart. Moving entries around in one of them leaves the nodes of the other untouched.
+    let pt = |i: i32| Point::new((i / 256 * 1000 + i % 16) as f64, (i % 256 / 16) as f64);
+    let mut rtree = RTree::bulk_load_hilbert_with_params(
+        (0..512).map(|i| (i, pt(i).envelope())).collect(),
+        RTreeParams::new(4),
+    );
+    let far = BoundingBox::new(500., -1., 2000., 100.);
+    let far_nodes = |rtree: &RTree<i32>| {
+        let mut nodes = vec![];
+        rtree.walk(&mut |payload| {
+            let bb = payload.entry.bounding_box();
+            if far.contains(bb) {
+                nodes.push((payload.id, bb.min, bb.max));
+            }
+        });
+        nodes
+    };
+    let mut near_ids = vec![];
+    rtree.walk(&mut |payload| {
+        if let RTreeNode::Leaf(i) = payload.entry.node() {
+            if *i < 256 {
+                near_ids.extend(payload.entry.id());
+            }
+        }
+    });
+    let before = far_nodes(&rtree);
+    assert!(!before.is_empty());
+
+    for (step, id) in near_ids.iter().step_by(3).enumerate() {
+        let moved = Point::new((step * 7 % 16) as f64, (step * 5 % 16) as f64 + 0.5);
+        assert!(rtree.update_bbox(*id, moved.envelope()));
+    }
+    assert_eq!(far_nodes(&rtree), before);
+    check_invariants(&rtree);
+}
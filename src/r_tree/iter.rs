@@ -0,0 +1,165 @@
+use super::{RTree, RTreeEntry, RTreeNode};
+use crate::{bounding_box::BoundingBox, scalar::Scalar};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, sawtooth_point};
+#[cfg(test)]
+use crate::object::RTreeObject;
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Iterates over all the entries with their bounding boxes, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, &BoundingBox<S, D>)> {
+        self.nodes.iter().filter_map(|entry| match entry.node {
+            RTreeNode::Leaf(ref value) => Some((value, &entry.bb)),
+            RTreeNode::Node(_) => None,
+        })
+    }
+
+    /// Iterates over all the entries with their bounding boxes, in no particular order.
+    ///
+    /// The bounding boxes cannot be changed this way, so the caller should not change the values in a way that
+    /// moves them out of their bounding boxes. Use [`RTree::update_bbox`] for that.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut T, &BoundingBox<S, D>)> {
+        self.nodes
+            .iter_mut()
+            .filter_map(|RTreeEntry { bb, node, .. }| match node {
+                RTreeNode::Leaf(value) => Some((value, &*bb)),
+                RTreeNode::Node(_) => None,
+            })
+    }
+
+    /// Returns the indices of the leaf nodes that intersect with the given bounding box.
+    pub(super) fn find_leaves(&self, bounding_box: &BoundingBox<S, D>) -> Vec<usize> {
+        let mut leaves = vec![];
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if !self.nodes[node].bb.intersects(bounding_box) {
+                continue;
+            }
+            match self.nodes[node].node {
+                RTreeNode::Leaf(_) => leaves.push(node),
+                RTreeNode::Node(ref children) => stack.extend(children.iter()),
+            }
+        }
+        leaves
+    }
+
+    /// Like [`RTree::find_multi`], but gives mutable references to the values.
+    ///
+    /// Same as [`RTree::get_mut`], the bounding boxes stay the same.
+    pub fn find_multi_mut(
+        &mut self,
+        bounding_box: &BoundingBox<S, D>,
+    ) -> impl Iterator<Item = &mut T> {
+        let mut leaves = self.find_leaves(bounding_box);
+        leaves.sort_unstable();
+        // Walk the arena from the front, splitting off each found entry so that they can be borrowed at once.
+        let mut rest = &mut self.nodes[..];
+        let mut offset = 0;
+        leaves.into_iter().map(move |leaf| {
+            let (entry, next) = std::mem::take(&mut rest)[leaf - offset..]
+                .split_first_mut()
+                .unwrap();
+            rest = next;
+            offset = leaf + 1;
+            match entry.node {
+                RTreeNode::Leaf(ref mut value) => value,
+                RTreeNode::Node(_) => panic!("Found a non-leaf node!!"),
+            }
+        })
+    }
+
+    /// Removes all the entries and returns them as an iterator, in no particular order.
+    ///
+    /// The tree keeps its parameters, and the ids of the removed entries are invalidated.
+    pub fn drain(&mut self) -> IntoIter<T, S, D> {
+        let ids: Vec<_> = self.nodes.iter().filter_map(|entry| entry.id).collect();
+        for id in ids {
+            self.release_id(id);
+        }
+        let nodes = std::mem::replace(&mut self.nodes, vec![Self::empty_root()]);
+        self.free_nodes.clear();
+        self.max_depth = 1;
+        self.len = 0;
+        IntoIter {
+            nodes: nodes.into_iter(),
+        }
+    }
+}
+
+/// An iterator that moves the values out of an [`RTree`], created by [`RTree::into_iter`] or [`RTree::drain`].
+pub struct IntoIter<T, S = f64, const D: usize = 2> {
+    nodes: std::vec::IntoIter<RTreeEntry<T, S, D>>,
+}
+
+impl<T, S, const D: usize> Iterator for IntoIter<T, S, D> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes.find_map(|entry| match entry.node {
+            RTreeNode::Leaf(value) => Some(value),
+            RTreeNode::Node(_) => None,
+        })
+    }
+}
+
+impl<T: Debug, S: Scalar, const D: usize> IntoIterator for RTree<T, S, D> {
+    type Item = T;
+    type IntoIter = IntoIter<T, S, D>;
+
+    /// Consumes the tree and iterates over the values, in no particular order.
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            nodes: self.nodes.into_iter(),
+        }
+    }
+}
+
+#[test]
+fn test_iter() {
+    let mut rtree = RTree::new();
+    let ids: Vec<_> = (0..20)
+        .map(|i| rtree.insert_entry(i, sawtooth_point(i).envelope()))
+        .collect();
+    rtree.remove(&sawtooth_point(5).envelope(), |_| true);
+
+    let mut values: Vec<_> = rtree
+        .iter()
+        .map(|(value, bb)| {
+            assert_eq!(bb.min, sawtooth_point(*value));
+            *value
+        })
+        .collect();
+    values.sort();
+    assert_eq!(values, (0..20).filter(|i| *i != 5).collect::<Vec<_>>());
+
+    for (value, _) in rtree.iter_mut() {
+        *value *= 10;
+    }
+    assert_eq!(rtree.get(ids[3]), Some(&30));
+
+    // Entries with x in [2, 4]
+    let region = BoundingBox::new(1.5, 0., 4.5, 20.);
+    for value in rtree.find_multi_mut(&region) {
+        *value += 1;
+    }
+    let mut found: Vec<_> = rtree.find_multi(&region).copied().collect();
+    found.sort();
+    assert_eq!(found, vec![21, 31, 41]);
+    assert_eq!(rtree.get(ids[1]), Some(&10));
+    check_invariants(&rtree);
+
+    let mut drained: Vec<_> = rtree.drain().collect();
+    drained.sort();
+    assert_eq!(drained.len(), 19);
+    assert!(rtree.is_empty());
+    assert_eq!(rtree.iter().count(), 0);
+    assert!(!rtree.contains(ids[0]));
+    let id = rtree.insert_entry(100, sawtooth_point(0).envelope());
+    assert_ne!(id, ids[0]);
+    check_invariants(&rtree);
+
+    let mut values: Vec<_> = rtree.into_iter().collect();
+    values.sort();
+    assert_eq!(values, vec![100]);
+}
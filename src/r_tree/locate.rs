@@ -0,0 +1,355 @@
+use super::{
+    selection::{SelectIntersects, SelectTouchesPoint, SelectionFunction},
+    RTree, RTreeNode,
+};
+use crate::{
+    bounding_box::BoundingBox,
+    object::{ExactGeometry, PointDistance},
+    point::Point,
+    scalar::Scalar,
+};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{
+    scattered_point,
+    selection::{SelectContainedBy, SelectContains, SelectDisjoint},
+    Circle, RTreeParams,
+};
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Walks down the nodes that `selection` unpacks, and yields the entries that it selects.
+    pub fn locate_with_selection<'a>(
+        &'a self,
+        selection: impl SelectionFunction<T, S, D> + 'a,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        let mut stack = vec![0];
+        std::iter::from_fn(move || {
+            while let Some(node) = stack.pop() {
+                let entry = &self.nodes[node];
+                match entry.node {
+                    RTreeNode::Leaf(ref value) => {
+                        if selection.is_selected(value, &entry.bb) {
+                            return Some(value);
+                        }
+                    }
+                    RTreeNode::Node(ref children) => {
+                        if selection.should_unpack_node(&entry.bb) {
+                            stack.extend(children.iter().rev());
+                        }
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    /// Finds the entries within `radius` of `point`, i.e. inside a circle in 2D.
+    ///
+    /// Nodes are pruned by the distance from the point to their bounding boxes, and each entry is checked with
+    /// its own [`PointDistance`], so entries whose boxes reach the circle but whose shapes do not are excluded.
+    pub fn locate_within_distance(
+        &self,
+        point: &Point<S, D>,
+        radius: f64,
+    ) -> impl Iterator<Item = &T>
+    where
+        T: PointDistance<S, D>,
+    {
+        self.locate_with_selection(WithinDistance {
+            point: *point,
+            radius2: radius * radius,
+        })
+    }
+
+    /// Finds the entries whose bounding boxes touch the segment from `a` to `b`.
+    ///
+    /// Nodes are pruned by the segment itself rather than by its enclosing box, which covers much more of the
+    /// space for a long diagonal segment.
+    pub fn locate_intersecting_segment(
+        &self,
+        a: &Point<S, D>,
+        b: &Point<S, D>,
+    ) -> impl Iterator<Item = &T> {
+        self.locate_along_polyline(&[*a, *b], 0.)
+    }
+
+    /// Finds the entries whose bounding boxes are within `half_width` of the polyline through `points`,
+    /// i.e. those touching the corridor around it.
+    ///
+    /// A single point is a polyline of length zero, and no points select nothing.
+    pub fn locate_along_polyline(
+        &self,
+        points: &[Point<S, D>],
+        half_width: f64,
+    ) -> impl Iterator<Item = &T> {
+        let segments = if points.len() == 1 {
+            vec![(points[0], points[0])]
+        } else {
+            points.windows(2).map(|w| (w[0], w[1])).collect()
+        };
+        self.locate_with_selection(AlongPolyline {
+            segments,
+            half_width,
+        })
+    }
+
+    /// Like [`RTree::find_multi`], but the candidates found by their bounding boxes are refined with
+    /// [`ExactGeometry::intersects_box`], so only the entries that actually intersect are returned.
+    pub fn find_multi_exact(&self, bounding_box: &BoundingBox<S, D>) -> impl Iterator<Item = &T>
+    where
+        T: ExactGeometry<S, D>,
+    {
+        let bounding_box = *bounding_box;
+        self.locate_with_selection(SelectIntersects(bounding_box))
+            .filter(move |value| value.intersects_box(&bounding_box))
+    }
+
+    /// Finds the entries that contain `point` by [`ExactGeometry::contains_point`], after filtering the candidates
+    /// by their bounding boxes.
+    pub fn locate_at_point(&self, point: &Point<S, D>) -> impl Iterator<Item = &T>
+    where
+        T: ExactGeometry<S, D>,
+    {
+        let point = *point;
+        self.locate_with_selection(SelectTouchesPoint(point))
+            .filter(move |value| value.contains_point(&point))
+    }
+}
+
+struct WithinDistance<S, const D: usize> {
+    point: Point<S, D>,
+    radius2: f64,
+}
+
+impl<T: PointDistance<S, D>, S: Scalar, const D: usize> SelectionFunction<T, S, D>
+    for WithinDistance<S, D>
+{
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.distance2_to_point(&self.point) <= self.radius2
+    }
+
+    fn is_selected(&self, value: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.distance2_to_point(&self.point) <= self.radius2
+            && value.distance2_to_point(&self.point) <= self.radius2
+    }
+}
+
+struct AlongPolyline<S, const D: usize> {
+    segments: Vec<(Point<S, D>, Point<S, D>)>,
+    half_width: f64,
+}
+
+impl<S: Scalar, const D: usize> AlongPolyline<S, D> {
+    fn touches(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        self.segments.iter().any(|(a, b)| {
+            if self.half_width <= 0. {
+                bounding_box.intersects_segment(a, b)
+            } else {
+                bounding_box.distance2_to_segment(a, b) <= self.half_width * self.half_width
+            }
+        })
+    }
+}
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for AlongPolyline<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        self.touches(bounding_box)
+    }
+
+    fn is_selected(&self, _value: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        self.touches(bounding_box)
+    }
+}
+
+#[test]
+fn test_locate_within_distance() {
+    let mut rtree = RTree::with_params(RTreeParams::new(6));
+    for i in 0..300 {
+        rtree.insert(scattered_point(i));
+    }
+    let center = Point::new(50., 50.);
+    let mut found: Vec<_> = rtree
+        .locate_within_distance(&center, 20.)
+        .map(|p| (p.x(), p.y()))
+        .collect();
+    found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut expected: Vec<_> = (0..300)
+        .map(scattered_point)
+        .filter(|p| {
+            let d = *p - center;
+            d.x() * d.x() + d.y() * d.y() <= 400.
+        })
+        .map(|p| (p.x(), p.y()))
+        .collect();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert!(!expected.is_empty());
+    assert_eq!(found, expected);
+
+    // The corners of the square are excluded.
+    let mut rtree = RTree::new();
+    rtree.insert(Point::new(9., 9.));
+    rtree.insert(Point::new(0., 9.5));
+    let found: Vec<_> = rtree
+        .locate_within_distance(&Point::new(0., 0.), 10.)
+        .collect();
+    assert_eq!(found, vec![&Point::new(0., 9.5)]);
+}
+
+#[test]
+fn test_locate_with_selection() {
+    let mut boxes = vec![];
+    for i in 0..10 {
+        for j in 0..10 {
+            let (x, y) = (i as f64 * 10., j as f64 * 10.);
+            // Boxes of the size 1 to 5
+            let size = ((i + j) % 5 + 1) as f64;
+            boxes.push(BoundingBox::new(x, y, x + size, y + size));
+        }
+    }
+    let mut rtree = RTree::with_params(RTreeParams::new(4));
+    for bb in &boxes {
+        rtree.insert(*bb);
+    }
+    fn check(
+        rtree: &RTree<BoundingBox>,
+        boxes: &[BoundingBox],
+        selection: impl SelectionFunction<BoundingBox> + Copy,
+    ) -> usize {
+        let count = rtree.locate_with_selection(selection).count();
+        let expected = boxes
+            .iter()
+            .filter(|bb| selection.is_selected(bb, bb))
+            .count();
+        assert_eq!(count, expected);
+        count
+    }
+
+    let region = BoundingBox::new(0., 0., 22., 22.);
+    assert_eq!(check(&rtree, &boxes, SelectIntersects(region)), 9);
+    assert_eq!(check(&rtree, &boxes, SelectContainedBy(region)), 4);
+    assert_eq!(check(&rtree, &boxes, SelectDisjoint(region)), 91);
+    let touches = |x, y| SelectTouchesPoint(Point::new(x, y));
+    assert_eq!(check(&rtree, &boxes, touches(10., 10.)), 1);
+    assert_eq!(check(&rtree, &boxes, touches(5., 5.)), 0);
+    let contains = |x0, y0, x1, y1| SelectContains(BoundingBox::new(x0, y0, x1, y1));
+    assert_eq!(check(&rtree, &boxes, contains(90.5, 90.5, 92., 92.)), 1);
+    assert_eq!(check(&rtree, &boxes, contains(90.5, 90.5, 99., 99.)), 0);
+
+    // A custom rule: large entries only, pruning nodes that are too small to hold one.
+    #[derive(Clone, Copy)]
+    struct MinArea(f64);
+    impl SelectionFunction<BoundingBox> for MinArea {
+        fn should_unpack_node(&self, bounding_box: &BoundingBox) -> bool {
+            self.0 <= bounding_box.get_area()
+        }
+        fn is_selected(&self, _: &BoundingBox, bounding_box: &BoundingBox) -> bool {
+            self.0 <= bounding_box.get_area()
+        }
+    }
+    assert_eq!(check(&rtree, &boxes, MinArea(16.)), 40);
+}
+
+#[test]
+fn test_exact_geometry() {
+    let mut rtree = RTree::with_params(RTreeParams::new(6));
+    for i in 0..300 {
+        rtree.insert(Circle::scattered(i));
+    }
+
+    // Only a corner of the bounding box of the circle reaches the query box.
+    let target = Circle::scattered(0);
+    let query = BoundingBox::new(-10., -10., -0.8, -0.8);
+    assert!(rtree.find_multi(&query).any(|c| *c == target));
+    assert!(!rtree.find_multi_exact(&query).any(|c| *c == target));
+    let region = BoundingBox::new(20., 20., 60., 60.);
+    let exact: Vec<_> = rtree.find_multi_exact(&region).collect();
+    assert_eq!(
+        exact.len(),
+        (0..300)
+            .filter(|i| Circle::scattered(*i).intersects_box(&region))
+            .count()
+    );
+
+    let pt = Point::new(0.6, 0.6);
+    let found: Vec<_> = rtree.locate_at_point(&pt).collect();
+    assert!(found.contains(&&target));
+    assert_eq!(
+        found.len(),
+        (0..300)
+            .filter(|i| Circle::scattered(*i).contains_point(&pt))
+            .count()
+    );
+    assert!(!rtree
+        .locate_at_point(&Point::new(-0.8, -0.8))
+        .any(|c| *c == target));
+}
+
+#[test]
+fn test_locate_along_polyline() {
+    let mut rtree = RTree::with_params(RTreeParams::new(4));
+    for i in 0..100 {
+        let (x, y) = ((i % 10) as f64 * 10., (i / 10) as f64 * 10.);
+        rtree.insert_entry(i, BoundingBox::new(x - 2., y - 2., x + 2., y + 2.));
+    }
+    let sorted = |it: &mut dyn Iterator<Item = &i32>| {
+        let mut v: Vec<_> = it.copied().collect();
+        v.sort();
+        v
+    };
+
+    // The diagonal segment only touches the boxes on the diagonal, though its enclosing box covers them all.
+    let (a, b) = (Point::new(0., 0.), Point::new(90., 90.));
+    let expected: Vec<_> = (0..10).map(|i| i * 11).collect();
+    assert_eq!(
+        sorted(&mut rtree.locate_intersecting_segment(&a, &b)),
+        expected
+    );
+    assert!(rtree
+        .locate_intersecting_segment(&Point::new(-5., 5.), &Point::new(5., -5.))
+        .eq([&0]));
+
+    // An L-shaped road along the bottom row and up the right column, and a wider corridor reaching the next row
+    let road = [
+        Point::new(0., 1.),
+        Point::new(91., 1.),
+        Point::new(91., 90.),
+    ];
+    let mut expected: Vec<_> = (0..10).chain((1..10).map(|i| i * 10 + 9)).collect();
+    expected.sort();
+    assert_eq!(
+        sorted(&mut rtree.locate_along_polyline(&road, 2.)),
+        expected
+    );
+    let mut expected: Vec<_> = (0..20).chain((2..10).map(|i| i * 10 + 9)).collect();
+    expected.sort();
+    assert_eq!(
+        sorted(&mut rtree.locate_along_polyline(&road, 7.)),
+        expected
+    );
+
+    // Brute force over the entries
+    let road = [
+        Point::new(-3., 40.),
+        Point::new(37., 12.),
+        Point::new(80., 66.),
+    ];
+    let expected: Vec<_> = rtree
+        .iter()
+        .filter(|(_, bb)| {
+            road.windows(2)
+                .any(|w| bb.distance2_to_segment(&w[0], &w[1]) <= 9.)
+        })
+        .map(|(v, _)| *v)
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(
+        sorted(&mut rtree.locate_along_polyline(&road, 3.)),
+        sorted(&mut expected.iter())
+    );
+
+    assert!(rtree
+        .locate_along_polyline(&[Point::new(50., 50.)], 1.)
+        .eq([&55]));
+    assert_eq!(rtree.locate_along_polyline(&[], 10.).count(), 0);
+}
@@ -0,0 +1,225 @@
+use super::{RTree, RTreeEntry, RTreeNode};
+use crate::{bounding_box::BoundingBox, scalar::Scalar};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, scattered_point, RTreeParams};
+#[cfg(test)]
+use crate::{object::RTreeObject, point::Point};
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Merges the entries of `other` into this tree and returns it.
+    ///
+    /// If both trees have the same `max_children`, `min_children` and `fat_margin`, the shorter tree is grafted into
+    /// the taller one as whole subtrees of the same height, so its leaves are not reinserted one by one.
+    /// Otherwise every entry of `other` is inserted, with its bounding box refattened by the margin of this tree.
+    ///
+    /// The ids of this tree stay valid, while the entries from `other` get new ids.
+    /// The parameters of this tree are kept.
+    pub fn merge(mut self, other: Self) -> Self {
+        if self.params.max_children != other.params.max_children
+            || self.params.min_children != other.params.min_children
+            || self.params.fat_margin != other.params.fat_margin
+        {
+            let other_margin = other.params.fat_margin;
+            for entry in other.nodes {
+                if let RTreeNode::Leaf(value) = entry.node {
+                    let bb = if 0. < other_margin {
+                        entry.bb.get_expanded(S::from_f64(-other_margin))
+                    } else {
+                        entry.bb
+                    };
+                    let (leaf, _) = self.append_leaf(value, bb);
+                    self.insert_node(leaf);
+                    self.len += 1;
+                }
+            }
+            return self;
+        }
+
+        let other_depth = other.max_depth;
+        self.len += other.len;
+        let short = self.adopt_nodes(other);
+        let mut short_height = other_depth;
+        if self.max_depth < other_depth {
+            // The root always lives at index 0, so the taller root takes its place.
+            self.nodes.swap(0, short);
+            for root in [0, short] {
+                if let RTreeNode::Node(children) = &self.nodes[root].node {
+                    for child in children.clone() {
+                        self.nodes[child].parent = Some(root);
+                    }
+                }
+            }
+            short_height = std::mem::replace(&mut self.max_depth, other_depth);
+        }
+
+        let RTreeNode::Node(children) =
+            std::mem::replace(&mut self.nodes[short].node, RTreeNode::Node(vec![]))
+        else {
+            panic!("The root is a leaf!!")
+        };
+        if short_height < self.max_depth && self.params.min_children <= children.len() {
+            // The shorter root is full enough to be an ordinary node in the taller tree.
+            self.nodes[short].node = RTreeNode::Node(children);
+            self.insert_subtree(short, short_height, &mut vec![]);
+        } else {
+            self.free_node(short);
+            for child in children {
+                self.nodes[child].parent = None;
+                self.insert_subtree(child, short_height - 1, &mut vec![]);
+            }
+        }
+        self
+    }
+
+    /// Moves all the nodes of `other` into the arena of this tree, giving the leaves new ids.
+    /// Returns the index of the root of `other`, which is detached.
+    fn adopt_nodes(&mut self, other: Self) -> usize {
+        let mut nodes: Vec<_> = other.nodes.into_iter().map(Some).collect();
+        let mut root = 0;
+        // (Index in `other`, new index of the parent)
+        let mut stack = vec![(0, None)];
+        while let Some((old, parent)) = stack.pop() {
+            let entry = nodes[old].take().unwrap();
+            let (node, children) = match entry.node {
+                RTreeNode::Leaf(value) => (RTreeNode::Leaf(value), vec![]),
+                RTreeNode::Node(children) => (RTreeNode::Node(vec![]), children),
+            };
+            let is_leaf = matches!(node, RTreeNode::Leaf(_));
+            let idx = self.append_entry(RTreeEntry {
+                bb: entry.bb,
+                parent,
+                id: None,
+                node,
+            });
+            if is_leaf {
+                self.allocate_id(idx);
+            }
+            match parent {
+                Some(parent) => {
+                    if let RTreeNode::Node(siblings) = &mut self.nodes[parent].node {
+                        siblings.push(idx);
+                    }
+                }
+                None => root = idx,
+            }
+            stack.extend(children.into_iter().rev().map(|child| (child, Some(idx))));
+        }
+        root
+    }
+
+    /// Removes the entries whose bounding boxes are inside the given bounding box, and returns them as a new tree
+    /// with the same parameters. Entries straddling the boundary stay in this tree.
+    ///
+    /// The new tree is bulk loaded, and the moved entries get new ids.
+    pub fn split_off(&mut self, bounding_box: &BoundingBox<S, D>) -> Self {
+        let leaves = self
+            .find_leaves(bounding_box)
+            .into_iter()
+            .filter(|leaf| bounding_box.contains(&self.nodes[*leaf].bb))
+            .collect();
+        let entries = self.remove_leaves(leaves);
+        let mut other = Self::with_params(self.params.clone());
+        let leaves = entries
+            .into_iter()
+            .map(|(value, bb)| other.append_fat_leaf(value, bb).0)
+            .collect();
+        other.pack_leaves(leaves, |rtree, ids| rtree.str_groups(ids));
+        other
+    }
+}
+
+#[test]
+fn test_merge() {
+    let bb = |i: i32| BoundingBox::from_center_size(scattered_point(i), Point::new(0.5, 0.5));
+    let all = BoundingBox::new(-1., -1., 102., 102.);
+    let build = |params: RTreeParams, range: std::ops::Range<i32>| {
+        let mut rtree = RTree::with_params(params);
+        let ids: Vec<_> = range.map(|i| rtree.insert_entry(i, bb(i))).collect();
+        (rtree, ids)
+    };
+
+    // Taller into shorter, shorter into taller, the same height, and an empty one
+    for (a, b) in [
+        (0..200, 200..210),
+        (0..10, 10..200),
+        (0..100, 100..200),
+        (0..0, 0..50),
+        (0..50, 50..50),
+    ] {
+        for params in [RTreeParams::new(4), RTreeParams::rstar(6)] {
+            let (left, ids) = build(params.clone(), a.clone());
+            let (right, _) = build(params, b.clone());
+            let rtree = left.merge(right);
+            check_invariants(&rtree);
+            assert_eq!(rtree.len(), a.len() + b.len());
+            let mut found: Vec<_> = rtree.find_multi(&all).copied().collect();
+            found.sort();
+            assert_eq!(found, (a.start..b.end).collect::<Vec<_>>());
+            for (i, id) in a.clone().zip(ids) {
+                assert_eq!(rtree.get(id), Some(&i));
+            }
+        }
+    }
+
+    // Different fan-outs fall back to insertion
+    let (left, _) = build(RTreeParams::new(4), 0..100);
+    let (right, _) = build(RTreeParams::new(8), 100..200);
+    let rtree = left.merge(right);
+    check_invariants(&rtree);
+    assert_eq!(rtree.find_multi(&all).count(), 200);
+
+    // So do different fat margins, and the boxes get the margin of the merged tree
+    for (margin, other_margin) in [(0., 1.), (1., 0.), (0.5, 2.)] {
+        let (left, _) = build(RTreeParams::new(4).fat_margin(margin), 0..100);
+        let (right, _) = build(RTreeParams::new(4).fat_margin(other_margin), 100..200);
+        let rtree = left.merge(right);
+        check_invariants(&rtree);
+        assert_eq!(rtree.find_multi(&all).count(), 200);
+        for (i, stored) in rtree.iter() {
+            let expected = bb(*i).get_expanded(margin);
+            for axis in 0..2 {
+                assert!((stored.min.coords[axis] - expected.min.coords[axis]).abs() < 1e-9);
+                assert!((stored.max.coords[axis] - expected.max.coords[axis]).abs() < 1e-9);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_split_off() {
+    let mut rtree = RTree::with_params(RTreeParams::new(6));
+    let ids: Vec<_> = (0..300)
+        .map(|i| rtree.insert_entry(i, scattered_point(i).envelope()))
+        .collect();
+    // Boxes straddling the boundary of the region stay
+    let straddling = [
+        rtree.insert_entry(300, BoundingBox::new(45., 10., 55., 12.)),
+        rtree.insert_entry(301, BoundingBox::new(-5., 60., 5., 61.)),
+    ];
+    let region = BoundingBox::new(0., 0., 50., 100.);
+    let inside: Vec<_> = (0..300)
+        .filter(|i| scattered_point(*i).x() <= 50.)
+        .collect();
+
+    let other = rtree.split_off(&region);
+    check_invariants(&rtree);
+    check_invariants(&other);
+    assert_eq!(other.len(), inside.len());
+    assert_eq!(rtree.len(), 302 - inside.len());
+    let mut found: Vec<_> = other.iter().map(|(value, _)| *value).collect();
+    found.sort();
+    assert_eq!(found, inside);
+    let mut left: Vec<_> = rtree.find_multi(&region).copied().collect();
+    left.sort();
+    assert_eq!(left, [300, 301]);
+    assert!(!rtree.contains(ids[0]));
+    assert_eq!(rtree.get(ids[2]), Some(&2));
+    assert_eq!(rtree.get(straddling[0]), Some(&300));
+
+    // And back together
+    let rtree = rtree.merge(other);
+    check_invariants(&rtree);
+    assert_eq!(rtree.len(), 302);
+}
@@ -0,0 +1,228 @@
+use super::{RTree, RTreeNode};
+use crate::{object::ExactGeometry, point::Point, scalar::Scalar};
+use std::{cmp::Ordering, collections::BinaryHeap, fmt::Debug};
+
+#[cfg(test)]
+use super::{scattered_point, Circle, RTreeParams};
+#[cfg(test)]
+use crate::{bounding_box::BoundingBox, object::RTreeObject};
+
+/// A node waiting in the priority queue of a best-first search.
+/// The order is reversed so that [`BinaryHeap`] pops the one with the smallest key first.
+pub(super) struct QueueItem {
+    /// The squared distance for a nearest neighbor search, or the ray parameter for ray casting
+    pub(super) key: f64,
+    pub(super) node: usize,
+    /// Whether `key` is computed from the exact geometry of a leaf rather than from its bounding box
+    pub(super) refined: bool,
+}
+
+impl PartialEq for QueueItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueItem {}
+
+impl PartialOrd for QueueItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.total_cmp(&self.key)
+    }
+}
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Finds the `k` entries nearest to `point` and returns them with their distances, nearest first.
+    ///
+    /// The distance of an entry is the Euclidean distance from `point` to its bounding box, so it is 0 for
+    /// the entries that contain the point. It visits the nodes in the order of the distance to their bounding
+    /// boxes, so it only looks at the part of the tree around the point.
+    pub fn nearest_neighbors(&self, point: &Point<S, D>, k: usize) -> Vec<(&T, f64)> {
+        self.nearest_iter(point).take(k).collect()
+    }
+
+    /// Iterates over the entries with their distances to `point` in increasing order of the distance,
+    /// as defined in [`RTree::nearest_neighbors`].
+    ///
+    /// The search advances only as far as the entries are taken, so the caller can stop on any condition,
+    /// e.g. at the first entry that passes a filter.
+    pub fn nearest_iter(&self, point: &Point<S, D>) -> impl Iterator<Item = (&T, f64)> {
+        NearestIter::new(self, *point, None)
+    }
+
+    /// [`RTree::nearest_neighbors`] by [`ExactGeometry::distance_to_point`] instead of the bounding boxes.
+    pub fn nearest_neighbors_exact(&self, point: &Point<S, D>, k: usize) -> Vec<(&T, f64)>
+    where
+        T: ExactGeometry<S, D>,
+    {
+        self.nearest_iter_exact(point).take(k).collect()
+    }
+
+    /// [`RTree::nearest_iter`] by [`ExactGeometry::distance_to_point`] instead of the bounding boxes.
+    ///
+    /// The distance to the bounding box is a lower bound of the exact distance, so an entry is put back in
+    /// the queue with its exact distance when it comes out first, and yielded when it comes out again.
+    pub fn nearest_iter_exact(&self, point: &Point<S, D>) -> impl Iterator<Item = (&T, f64)>
+    where
+        T: ExactGeometry<S, D>,
+    {
+        NearestIter::new(self, *point, Some(T::distance_to_point))
+    }
+}
+
+struct NearestIter<'a, T, S, const D: usize> {
+    this: &'a RTree<T, S, D>,
+    point: Point<S, D>,
+    /// The exact distance of an entry, or `None` to use the bounding box
+    exact: Option<fn(&T, &Point<S, D>) -> f64>,
+    /// The nodes and entries found so far but not yielded yet
+    queue: BinaryHeap<QueueItem>,
+}
+
+impl<'a, T, S: Scalar, const D: usize> NearestIter<'a, T, S, D> {
+    fn new(
+        this: &'a RTree<T, S, D>,
+        point: Point<S, D>,
+        exact: Option<fn(&T, &Point<S, D>) -> f64>,
+    ) -> Self {
+        let queue = BinaryHeap::from([QueueItem {
+            key: this.nodes[0].bb.distance2_to_point(&point),
+            node: 0,
+            refined: false,
+        }]);
+        Self {
+            this,
+            point,
+            exact,
+            queue,
+        }
+    }
+
+    fn next_nearest(&mut self) -> Option<(&'a T, f64)> {
+        loop {
+            let QueueItem {
+                key: distance2,
+                node,
+                refined,
+            } = self.queue.pop()?;
+            match self.this.nodes[node].node {
+                RTreeNode::Leaf(ref value) => match self.exact {
+                    Some(exact) if !refined => {
+                        let distance = exact(value, &self.point);
+                        self.queue.push(QueueItem {
+                            key: distance * distance,
+                            node,
+                            refined: true,
+                        });
+                    }
+                    _ => return Some((value, distance2.sqrt())),
+                },
+                RTreeNode::Node(ref children) => {
+                    self.queue.extend(children.iter().map(|child| QueueItem {
+                        key: self.this.nodes[*child].bb.distance2_to_point(&self.point),
+                        node: *child,
+                        refined: false,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, S: Scalar, const D: usize> Iterator for NearestIter<'a, T, S, D> {
+    type Item = (&'a T, f64);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_nearest()
+    }
+}
+
+#[test]
+fn test_nearest_neighbors() {
+    let mut rtree = RTree::with_params(RTreeParams::new(6));
+    for i in 0..300 {
+        rtree.insert_entry(i, scattered_point(i).envelope());
+    }
+    let query = Point::new(40.3, 60.7);
+    let distance = |i: i32| {
+        let d = scattered_point(i) - query;
+        (d.x() * d.x() + d.y() * d.y()).sqrt()
+    };
+    let mut expected: Vec<_> = (0..300).collect();
+    expected.sort_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+
+    let found = rtree.nearest_neighbors(&query, 10);
+    assert_eq!(
+        found.iter().map(|(value, _)| **value).collect::<Vec<_>>(),
+        expected[..10]
+    );
+    for (value, d) in found {
+        assert!((d - distance(*value)).abs() < 1e-9);
+    }
+    assert_eq!(rtree.nearest_neighbors(&query, 1000).len(), 300);
+    assert!(rtree.nearest_neighbors(&query, 0).is_empty());
+
+    // An entry whose box contains the point is at distance 0.
+    rtree.insert_entry(1000, BoundingBox::new(40., 60., 41., 61.));
+    assert_eq!(rtree.nearest_neighbors(&query, 1), vec![(&1000, 0.)]);
+
+    let rtree: RTree<i32> = RTree::new();
+    assert!(rtree.nearest_neighbors(&query, 3).is_empty());
+}
+
+#[test]
+fn test_nearest_iter() {
+    let mut rtree = RTree::with_params(RTreeParams::rstar(6));
+    for i in 0..300 {
+        rtree.insert_entry(
+            i,
+            BoundingBox::from_center_size(scattered_point(i), Point::new(0.5, 0.5)),
+        );
+    }
+    let query = Point::new(70., 20.);
+    let distances: Vec<_> = rtree.nearest_iter(&query).map(|(_, d)| d).collect();
+    assert_eq!(distances.len(), 300);
+    assert!(distances.windows(2).all(|w| w[0] <= w[1]));
+
+    // The first one that passes a filter
+    let (value, d) = rtree
+        .nearest_iter(&query)
+        .find(|(value, _)| **value % 7 == 0)
+        .unwrap();
+    let expected = rtree
+        .nearest_iter(&query)
+        .filter(|(value, _)| **value % 7 == 0)
+        .map(|(_, d)| d)
+        .fold(f64::INFINITY, f64::min);
+    assert_eq!(value % 7, 0);
+    assert_eq!(d, expected);
+}
+
+#[test]
+fn test_nearest_exact() {
+    let mut rtree = RTree::with_params(RTreeParams::new(6));
+    for i in 0..300 {
+        rtree.insert(Circle::scattered(i));
+    }
+
+    let query = Point::new(50.5, 49.5);
+    let mut expected: Vec<_> = (0..300)
+        .map(|i| Circle::scattered(i).distance_to_point(&query))
+        .collect();
+    expected.sort_by(f64::total_cmp);
+    let found: Vec<_> = rtree
+        .nearest_neighbors_exact(&query, 20)
+        .into_iter()
+        .map(|(c, d)| {
+            assert_eq!(c.distance_to_point(&query), d);
+            d
+        })
+        .collect();
+    assert_eq!(found, expected[..20]);
+    assert_eq!(rtree.nearest_iter_exact(&query).count(), 300);
+}
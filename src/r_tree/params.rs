@@ -0,0 +1,113 @@
+use super::split::{ExhaustiveSplit, RStarSplit, SplitStrategy};
+use std::sync::Arc;
+
+/// Tuning parameters of an [`RTree`](super::RTree).
+///
+/// ```
+/// use rustree::{QuadraticSplit, RTree, RTreeParams};
+/// let rtree: RTree<()> = RTree::with_params(
+///     RTreeParams::new(16)
+///         .min_children(4)
+///         .split_strategy(QuadraticSplit),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct RTreeParams<const D: usize = 2> {
+    pub(super) max_children: usize,
+    pub(super) min_children: usize,
+    pub(super) split_strategy: Arc<dyn SplitStrategy<D>>,
+    pub(super) insertion: InsertionMode,
+    pub(super) fat_margin: f64,
+}
+
+/// How [`RTree`](super::RTree) chooses the place of a new entry and treats an overflowing node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InsertionMode {
+    /// Descend into the child whose bounding box has the least area after including the new entry,
+    /// and split a node as soon as it overflows.
+    #[default]
+    Guttman,
+    /// R*-tree insertion. Above the leaves, descend into the child whose overlap with its siblings grows the least.
+    /// The first time a node overflows at each level in an insertion, about 30% of its children farthest from
+    /// the center are reinserted instead of splitting it. It usually gives less overlap at the cost of slower insertion.
+    RStar,
+}
+
+impl<const D: usize> RTreeParams<D> {
+    /// Creates parameters with the given maximum number of children per node (fan-out).
+    /// The minimum fill defaults to half of it.
+    pub fn new(max_children: usize) -> Self {
+        Self {
+            max_children,
+            min_children: max_children / 2,
+            split_strategy: Arc::new(ExhaustiveSplit),
+            insertion: InsertionMode::Guttman,
+            fat_margin: 0.,
+        }
+    }
+
+    /// Parameters for an R*-tree, which is [`InsertionMode::RStar`] combined with [`RStarSplit`].
+    pub fn rstar(max_children: usize) -> Self {
+        Self::new(max_children)
+            .insertion(InsertionMode::RStar)
+            .split_strategy(RStarSplit)
+    }
+
+    /// Sets the minimum number of children a non-root node keeps. Splits never produce a node smaller than this,
+    /// and a node that falls below it after a removal is dissolved.
+    pub fn min_children(mut self, min_children: usize) -> Self {
+        self.min_children = min_children;
+        self
+    }
+
+    /// Sets the algorithm to split an overflowing node. The default is [`ExhaustiveSplit`].
+    pub fn split_strategy(mut self, split_strategy: impl SplitStrategy<D> + 'static) -> Self {
+        self.split_strategy = Arc::new(split_strategy);
+        self
+    }
+
+    pub fn insertion(mut self, insertion: InsertionMode) -> Self {
+        self.insertion = insertion;
+        self
+    }
+
+    /// Sets the margin added to every side of the bounding boxes of the entries when they are stored.
+    /// It is converted to the scalar type of the tree, rounding toward zero for integers.
+    ///
+    /// With a positive margin, [`RTree::update_bbox`](super::RTree::update_bbox) with a box that moved by less than
+    /// the margin does not touch the tree at all, which helps with objects that move a little every frame.
+    /// The cost is larger boxes and so more false positives in queries.
+    pub fn fat_margin(mut self, fat_margin: f64) -> Self {
+        self.fat_margin = fat_margin;
+        self
+    }
+
+    pub fn get_max_children(&self) -> usize {
+        self.max_children
+    }
+
+    pub fn get_min_children(&self) -> usize {
+        self.min_children
+    }
+
+    pub fn get_fat_margin(&self) -> f64 {
+        self.fat_margin
+    }
+
+    /// Panics if the combination of parameters cannot make a valid tree.
+    pub(super) fn validate(&self) {
+        assert!(2 <= self.max_children, "max_children must be at least 2");
+        assert!(1 <= self.min_children, "min_children must be at least 1");
+        assert!(
+            self.min_children * 2 <= self.max_children + 1,
+            "min_children must be at most half of max_children + 1 so that a split can satisfy it"
+        );
+        assert!(0. <= self.fat_margin, "fat_margin must not be negative");
+    }
+}
+
+impl<const D: usize> Default for RTreeParams<D> {
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
@@ -0,0 +1,201 @@
+use super::{nearest::QueueItem, RTree, RTreeNode};
+use crate::{object::RayIntersection, point::Point, scalar::Scalar};
+use std::{collections::BinaryHeap, fmt::Debug};
+
+#[cfg(test)]
+use super::{Circle, RTreeParams};
+
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    /// Casts the ray `origin + t * direction` for `0 <= t <= max_t` and iterates over the entries whose bounding
+    /// boxes it hits, with the `t` at which it enters them, in increasing order of `t`.
+    ///
+    /// Nodes are visited in the order the ray enters their bounding boxes, so taking only the first few hits
+    /// looks at a small part of the tree.
+    pub fn raycast(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> impl Iterator<Item = (&T, f64)> {
+        RayIter::new(self, *origin, *direction, max_t, None)
+    }
+
+    /// Returns the first entry that the ray hits by its bounding box, as in [`RTree::raycast`].
+    pub fn raycast_first(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> Option<(&T, f64)> {
+        self.raycast(origin, direction, max_t).next()
+    }
+
+    /// [`RTree::raycast`] with the hits refined by [`RayIntersection`] of the entries.
+    ///
+    /// An entry whose bounding box the ray hits but whose shape it misses is skipped, and the hits are ordered
+    /// by the exact `t`.
+    pub fn raycast_exact(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> impl Iterator<Item = (&T, f64)>
+    where
+        T: RayIntersection<S, D>,
+    {
+        RayIter::new(self, *origin, *direction, max_t, Some(T::intersect_ray))
+    }
+
+    /// Returns the first entry that the ray hits, as in [`RTree::raycast_exact`].
+    pub fn raycast_first_exact(
+        &self,
+        origin: &Point<S, D>,
+        direction: &Point<S, D>,
+        max_t: f64,
+    ) -> Option<(&T, f64)>
+    where
+        T: RayIntersection<S, D>,
+    {
+        self.raycast_exact(origin, direction, max_t).next()
+    }
+}
+
+type ExactRay<T, S, const D: usize> = fn(&T, &Point<S, D>, &Point<S, D>, f64) -> Option<f64>;
+
+struct RayIter<'a, T, S, const D: usize> {
+    this: &'a RTree<T, S, D>,
+    origin: Point<S, D>,
+    direction: Point<S, D>,
+    max_t: f64,
+    /// The exact intersection of an entry, or `None` to use the bounding box
+    exact: Option<ExactRay<T, S, D>>,
+    /// The nodes and entries hit so far but not yielded yet
+    queue: BinaryHeap<QueueItem>,
+}
+
+impl<'a, T, S: Scalar, const D: usize> RayIter<'a, T, S, D> {
+    fn new(
+        this: &'a RTree<T, S, D>,
+        origin: Point<S, D>,
+        direction: Point<S, D>,
+        max_t: f64,
+        exact: Option<ExactRay<T, S, D>>,
+    ) -> Self {
+        let mut iter = Self {
+            this,
+            origin,
+            direction,
+            max_t,
+            exact,
+            queue: BinaryHeap::new(),
+        };
+        iter.push(0);
+        iter
+    }
+
+    /// Queues the node if the ray hits its bounding box.
+    fn push(&mut self, node: usize) {
+        if let Some(t) =
+            self.this.nodes[node]
+                .bb
+                .intersect_ray(&self.origin, &self.direction, self.max_t)
+        {
+            self.queue.push(QueueItem {
+                key: t,
+                node,
+                refined: false,
+            });
+        }
+    }
+
+    fn next_hit(&mut self) -> Option<(&'a T, f64)> {
+        loop {
+            let QueueItem {
+                key: t,
+                node,
+                refined,
+            } = self.queue.pop()?;
+            match self.this.nodes[node].node {
+                RTreeNode::Leaf(ref value) => match self.exact {
+                    Some(exact) if !refined => {
+                        if let Some(t) = exact(value, &self.origin, &self.direction, self.max_t) {
+                            self.queue.push(QueueItem {
+                                key: t,
+                                node,
+                                refined: true,
+                            });
+                        }
+                    }
+                    _ => return Some((value, t)),
+                },
+                RTreeNode::Node(ref children) => {
+                    for child in children {
+                        self.push(*child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, S: Scalar, const D: usize> Iterator for RayIter<'a, T, S, D> {
+    type Item = (&'a T, f64);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_hit()
+    }
+}
+
+#[test]
+fn test_raycast() {
+    let mut rtree = RTree::with_params(RTreeParams::new(4));
+    for i in 0..100 {
+        let (x, y) = ((i % 10) as f64 * 10., (i / 10) as f64 * 10.);
+        rtree.insert(Circle {
+            center: Point::new(x, y),
+            radius: 2.,
+        });
+    }
+
+    // Along the row at y = 30 from the left
+    let origin = Point::new(-10., 31.);
+    let direction = Point::new(1., 0.);
+    let hits: Vec<_> = rtree
+        .raycast(&origin, &direction, 1000.)
+        .map(|(c, t)| (c.center.x(), t))
+        .collect();
+    let expected: Vec<_> = (0..10)
+        .map(|i| (i as f64 * 10., i as f64 * 10. + 8.))
+        .collect();
+    assert_eq!(hits, expected);
+    assert_eq!(rtree.raycast(&origin, &direction, 25.).count(), 2);
+    let (first, t) = rtree.raycast_first(&origin, &direction, 1000.).unwrap();
+    assert_eq!((first.center, t), (Point::new(0., 30.), 8.));
+    assert!(rtree
+        .raycast_first(&origin, &Point::new(-1., 0.), 1000.)
+        .is_none());
+
+    // A diagonal ray through the circles near the diagonal
+    let origin = Point::new(-5., -3.);
+    let direction = Point::new(1., 1.);
+    let exact: Vec<_> = rtree
+        .raycast_exact(&origin, &direction, 1000.)
+        .map(|(c, t)| {
+            let d = c.center - (origin + Point::new(t, t));
+            assert!(((d.x() * d.x() + d.y() * d.y()).sqrt() - 2.).abs() < 1e-9);
+            c.center
+        })
+        .collect();
+    let expected: Vec<_> = (0..10)
+        .map(|i| Point::new(i as f64 * 10., i as f64 * 10.))
+        .collect();
+    assert_eq!(exact, expected);
+    let (first, _) = rtree
+        .raycast_first_exact(&origin, &direction, 1000.)
+        .unwrap();
+    assert_eq!(first.center, Point::new(0., 0.));
+
+    // A ray through the corners of the boxes above the diagonal, which misses the circles
+    let origin = Point::new(-7., 0.);
+    assert_eq!(rtree.raycast(&origin, &direction, 1000.).count(), 9);
+    assert_eq!(rtree.raycast_exact(&origin, &direction, 1000.).count(), 0);
+}
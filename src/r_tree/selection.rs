@@ -0,0 +1,87 @@
+//! Predicates for [`RTree::locate_with_selection`](super::RTree::locate_with_selection).
+
+use crate::{bounding_box::BoundingBox, point::Point, scalar::Scalar};
+
+/// Decides which part of an [`RTree`](super::RTree) a query walks and which entries it yields.
+///
+/// `should_unpack_node` must return true for every node that may have a selected entry below it,
+/// otherwise the entries are missed. The tighter it is, the fewer nodes the query visits.
+pub trait SelectionFunction<T, S: Scalar = f64, const D: usize = 2> {
+    /// Returns true if the query should look into the children of a node with the given bounding box.
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool;
+
+    /// Returns true if the entry should be yielded.
+    fn is_selected(&self, value: &T, bounding_box: &BoundingBox<S, D>) -> bool;
+}
+
+/// Selects the entries whose bounding boxes intersect with the given one, like [`RTree::find_multi`](super::RTree::find_multi).
+#[derive(Clone, Copy, Debug)]
+pub struct SelectIntersects<S = f64, const D: usize = 2>(pub BoundingBox<S, D>);
+
+/// Selects the entries whose bounding boxes contain the given one.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectContains<S = f64, const D: usize = 2>(pub BoundingBox<S, D>);
+
+/// Selects the entries whose bounding boxes are inside the given one.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectContainedBy<S = f64, const D: usize = 2>(pub BoundingBox<S, D>);
+
+/// Selects the entries whose bounding boxes contain the given point, including on the boundary.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectTouchesPoint<S = f64, const D: usize = 2>(pub Point<S, D>);
+
+/// Selects the entries whose bounding boxes do not intersect with the given one.
+///
+/// Only the nodes entirely inside the box can be skipped, so it usually visits most of the tree.
+#[derive(Clone, Copy, Debug)]
+pub struct SelectDisjoint<S = f64, const D: usize = 2>(pub BoundingBox<S, D>);
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for SelectIntersects<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.intersects(&self.0)
+    }
+
+    fn is_selected(&self, _: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.intersects(&self.0)
+    }
+}
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for SelectContains<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.contains(&self.0)
+    }
+
+    fn is_selected(&self, _: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.contains(&self.0)
+    }
+}
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for SelectContainedBy<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.intersects(&self.0)
+    }
+
+    fn is_selected(&self, _: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        self.0.contains(bounding_box)
+    }
+}
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for SelectTouchesPoint<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.contains_point(&self.0)
+    }
+
+    fn is_selected(&self, _: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        bounding_box.contains_point(&self.0)
+    }
+}
+
+impl<T, S: Scalar, const D: usize> SelectionFunction<T, S, D> for SelectDisjoint<S, D> {
+    fn should_unpack_node(&self, bounding_box: &BoundingBox<S, D>) -> bool {
+        !self.0.contains(bounding_box)
+    }
+
+    fn is_selected(&self, _: &T, bounding_box: &BoundingBox<S, D>) -> bool {
+        !bounding_box.intersects(&self.0)
+    }
+}
@@ -0,0 +1,291 @@
+//! Algorithms to split the children of an overflowing node into 2 groups.
+//!
+//! A strategy takes the bounding boxes of the children and returns a partition as a `Vec<bool>`,
+//! where `true` means the child goes to the new sibling node.
+//! The bounding boxes are converted to `f64` whatever the scalar type of the tree is,
+//! so that a strategy only needs to be written once.
+//! Both groups must have at least `min_children` entries.
+
+use crate::{bounding_box::BoundingBox, point::Point};
+use std::fmt::Debug;
+
+#[cfg(test)]
+use super::{check_invariants, scattered_point, RTree, RTreeParams};
+
+/// A method to split an overflowing node, chosen per tree with
+/// [`RTreeParams::split_strategy`](super::RTreeParams::split_strategy).
+///
+/// Faster strategies build the tree quicker, while better ones produce less overlap and faster queries.
+pub trait SplitStrategy<const D: usize = 2>: Debug + Send + Sync {
+    /// Partitions `bbs` into 2 groups. Returns `true` for the entries that go to the new sibling node.
+    ///
+    /// Both groups must have at least `min_children` entries, which is guaranteed to be possible.
+    fn split(&self, bbs: &[BoundingBox<f64, D>], min_children: usize) -> Vec<bool>;
+}
+
+/// Guttman's linear split. It is the fastest, but produces the most overlap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinearSplit;
+
+/// Guttman's quadratic split. A good balance between the build time and the query performance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuadraticSplit;
+
+/// Scans all combinations and takes the one with the minimum sum of areas.
+///
+/// The number of combinations grows exponentially, so it falls back to [`QuadraticSplit`] if there are more than
+/// [`ExhaustiveSplit::LIMIT`] entries.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExhaustiveSplit;
+
+/// The split of R*-tree. It chooses the axis with the least margin, then the distribution along the axis
+/// with the least overlap.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RStarSplit;
+
+impl<const D: usize> SplitStrategy<D> for LinearSplit {
+    fn split(&self, bbs: &[BoundingBox<f64, D>], min_children: usize) -> Vec<bool> {
+        // Find the pair of entries with the greatest normalized separation along any axis.
+        let mut seeds = (0, 1);
+        let mut max_separation = f64::NEG_INFINITY;
+        for axis in 0..D {
+            let axis = |p: &Point<f64, D>| p.coords[axis];
+            let highest_low = (0..bbs.len())
+                .max_by(|a, b| axis(&bbs[*a].min).total_cmp(&axis(&bbs[*b].min)))
+                .unwrap();
+            let Some(lowest_high) = (0..bbs.len())
+                .filter(|i| *i != highest_low)
+                .min_by(|a, b| axis(&bbs[*a].max).total_cmp(&axis(&bbs[*b].max)))
+            else {
+                continue;
+            };
+            let width = bbs.iter().map(|bb| axis(&bb.max)).fold(f64::MIN, f64::max)
+                - bbs.iter().map(|bb| axis(&bb.min)).fold(f64::MAX, f64::min);
+            let separation = axis(&bbs[highest_low].min) - axis(&bbs[lowest_high].max);
+            let separation = if 0. < width {
+                separation / width
+            } else {
+                separation
+            };
+            if max_separation < separation {
+                max_separation = separation;
+                seeds = (lowest_high, highest_low);
+            }
+        }
+
+        distribute(bbs, seeds, min_children, |_, assigned| {
+            assigned.iter().position(|a| a.is_none()).unwrap()
+        })
+    }
+}
+
+impl<const D: usize> SplitStrategy<D> for QuadraticSplit {
+    fn split(&self, bbs: &[BoundingBox<f64, D>], min_children: usize) -> Vec<bool> {
+        // Pick the pair of entries that would waste the most area if put together as seeds.
+        let mut seeds = (0, 1);
+        let mut max_waste = f64::NEG_INFINITY;
+        for i in 0..bbs.len() {
+            for j in i + 1..bbs.len() {
+                let waste = bbs[i].get_union(&bbs[j]).get_volume()
+                    - bbs[i].get_volume()
+                    - bbs[j].get_volume();
+                if max_waste < waste {
+                    max_waste = waste;
+                    seeds = (i, j);
+                }
+            }
+        }
+
+        // Then assign the entry with the strongest preference for one group first.
+        distribute(bbs, seeds, min_children, |group_bb, assigned| {
+            (0..bbs.len())
+                .filter(|i| assigned[*i].is_none())
+                .max_by(|a, b| {
+                    let [a0, a1] = enlargements(group_bb, &bbs[*a]);
+                    let [b0, b1] = enlargements(group_bb, &bbs[*b]);
+                    (a0 - a1).abs().total_cmp(&(b0 - b1).abs())
+                })
+                .unwrap()
+        })
+    }
+}
+
+impl ExhaustiveSplit {
+    pub const LIMIT: usize = 10;
+}
+
+impl<const D: usize> SplitStrategy<D> for ExhaustiveSplit {
+    fn split(&self, bbs: &[BoundingBox<f64, D>], min_children: usize) -> Vec<bool> {
+        if Self::LIMIT < bbs.len() {
+            return SplitStrategy::<D>::split(&QuadraticSplit, bbs, min_children);
+        }
+        // Our goal here is to split the collection of nodes into 2 groups that minimizes each bounding box.
+        // I don't know if there is any fast way to find the solution, so I will just scan all possible combinations.
+        let all_combi = 1u32 << bbs.len();
+        // combi represents the combinations of node sets in a bitfield. If a bit is on, it is right side.
+        // Combinations that leave either side with less than `min_children` are skipped.
+        // Technically, it counts the same combination twice if you flip all bits.
+        let combi = (1..all_combi - 1)
+            .filter(|combi| {
+                let right = combi.count_ones() as usize;
+                min_children <= right && min_children <= bbs.len() - right
+            })
+            .map(|combi| {
+                let group_area = |side| {
+                    bbs.iter()
+                        .enumerate()
+                        .filter(|(i, _)| (combi & (1 << *i) != 0) == side)
+                        .map(|(_, bb)| *bb)
+                        .reduce(|a, b| a.get_union(&b))
+                        .map(|bb| bb.get_volume())
+                        .unwrap_or(0.)
+                };
+                (combi, group_area(false) + group_area(true))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((combi, _)) = combi else {
+            panic!("No combination is found")
+        };
+        (0..bbs.len()).map(|i| combi & (1 << i) != 0).collect()
+    }
+}
+
+impl<const D: usize> SplitStrategy<D> for RStarSplit {
+    fn split(&self, bbs: &[BoundingBox<f64, D>], min_children: usize) -> Vec<bool> {
+        // Candidate orders along an axis: sorted by the lower and by the upper values.
+        let sorted_orders = |axis: usize| {
+            let axis = |p: &Point<f64, D>| p.coords[axis];
+            let mut by_min: Vec<usize> = (0..bbs.len()).collect();
+            by_min.sort_by(|a, b| axis(&bbs[*a].min).total_cmp(&axis(&bbs[*b].min)));
+            let mut by_max: Vec<usize> = (0..bbs.len()).collect();
+            by_max.sort_by(|a, b| axis(&bbs[*a].max).total_cmp(&axis(&bbs[*b].max)));
+            [by_min, by_max]
+        };
+        let group_bbs = |order: &[usize], k: usize| {
+            let union = |ids: &[usize]| {
+                ids.iter()
+                    .map(|i| bbs[*i])
+                    .reduce(|a, b| a.get_union(&b))
+                    .unwrap()
+            };
+            (union(&order[..k]), union(&order[k..]))
+        };
+        let distributions = min_children..=bbs.len() - min_children;
+
+        // Choose the axis with the least sum of margins over all distributions.
+        let orders = (0..D)
+            .map(sorted_orders)
+            .min_by(|a, b| {
+                let margin_sum = |orders: &[Vec<usize>; 2]| {
+                    orders
+                        .iter()
+                        .flat_map(|order| {
+                            distributions.clone().map(|k| {
+                                let (left, right) = group_bbs(order, k);
+                                left.get_margin() + right.get_margin()
+                            })
+                        })
+                        .sum::<f64>()
+                };
+                margin_sum(a).total_cmp(&margin_sum(b))
+            })
+            .unwrap();
+
+        // Along the axis, choose the distribution with the least overlap, then the least area.
+        let (order, k, _) = orders
+            .iter()
+            .flat_map(|order| {
+                distributions.clone().map(move |k| {
+                    let (left, right) = group_bbs(order, k);
+                    let overlap = left
+                        .get_intersection(&right)
+                        .map(|bb| bb.get_volume())
+                        .unwrap_or(0.);
+                    (order, k, (overlap, left.get_volume() + right.get_volume()))
+                })
+            })
+            .min_by(|(_, _, a), (_, _, b)| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+            .unwrap();
+
+        let mut partition = vec![false; bbs.len()];
+        for i in &order[k..] {
+            partition[*i] = true;
+        }
+        partition
+    }
+}
+
+/// Volume enlargements of each group's bounding box to include `bb`.
+fn enlargements<const D: usize>(
+    group_bb: &[BoundingBox<f64, D>; 2],
+    bb: &BoundingBox<f64, D>,
+) -> [f64; 2] {
+    [0, 1].map(|side| group_bb[side].get_union(bb).get_volume() - group_bb[side].get_volume())
+}
+
+/// Assigns the entries other than `seeds` to the 2 groups one by one in the order `pick_next` gives,
+/// each to the group whose bounding box needs the least enlargement.
+fn distribute<const D: usize>(
+    bbs: &[BoundingBox<f64, D>],
+    seeds: (usize, usize),
+    min_children: usize,
+    pick_next: impl Fn(&[BoundingBox<f64, D>; 2], &[Option<bool>]) -> usize,
+) -> Vec<bool> {
+    let mut assigned: Vec<Option<bool>> = vec![None; bbs.len()];
+    assigned[seeds.0] = Some(false);
+    assigned[seeds.1] = Some(true);
+    let mut group_bb = [bbs[seeds.0], bbs[seeds.1]];
+    let mut group_len = [1, 1];
+    let mut remaining = bbs.len() - 2;
+
+    while 0 < remaining {
+        // If one group needs all the rest to reach the minimum fill, give them all to it.
+        if let Some(side) = (0..2).find(|side| group_len[*side] + remaining <= min_children) {
+            for a in assigned.iter_mut().filter(|a| a.is_none()) {
+                *a = Some(side == 1);
+            }
+            break;
+        }
+
+        let next = pick_next(&group_bb, &assigned);
+        let [d0, d1] = enlargements(&group_bb, &bbs[next]);
+        // Ties are resolved by the smaller area, then the fewer entries.
+        let side = if d0 != d1 {
+            (d1 < d0) as usize
+        } else if group_bb[0].get_volume() != group_bb[1].get_volume() {
+            (group_bb[1].get_volume() < group_bb[0].get_volume()) as usize
+        } else {
+            (group_len[1] < group_len[0]) as usize
+        };
+        assigned[next] = Some(side == 1);
+        group_bb[side] = group_bb[side].get_union(&bbs[next]);
+        group_len[side] += 1;
+        remaining -= 1;
+    }
+
+    assigned.into_iter().map(|a| a.unwrap()).collect()
+}
+
+#[test]
+fn test_split_strategies() {
+    let strategies: [&dyn Fn(RTreeParams) -> RTreeParams; 4] = [
+        &|params| params.split_strategy(LinearSplit),
+        &|params| params.split_strategy(QuadraticSplit),
+        &|params| params.split_strategy(ExhaustiveSplit),
+        &|params| params.split_strategy(RStarSplit),
+    ];
+    for strategy in strategies {
+        let mut rtree = RTree::with_params(strategy(RTreeParams::new(8)));
+        for i in 0..300 {
+            let pt = scattered_point(i);
+            rtree.insert_entry(i, BoundingBox::from_center_size(pt, Point::new(0.5, 0.5)));
+        }
+        check_invariants(&rtree);
+        assert_eq!(
+            rtree
+                .find_multi(&BoundingBox::new(-1., -1., 102., 102.))
+                .count(),
+            300
+        );
+    }
+}
@@ -1,15 +1,16 @@
 use super::{RTree, RTreeEntry, RTreeNode};
+use crate::scalar::Scalar;
 use std::fmt::Debug;
 
 #[non_exhaustive]
-pub struct WalkCallbackPayload<'a, T> {
+pub struct WalkCallbackPayload<'a, T, S = f64, const D: usize = 2> {
     pub id: usize,
     pub level: usize,
-    pub entry: &'a RTreeEntry<T>,
+    pub entry: &'a RTreeEntry<T, S, D>,
 }
 
-impl<T: Debug> RTree<T> {
-    fn walk_rec(&self, id: usize, level: usize, f: &mut impl FnMut(&WalkCallbackPayload<T>)) {
+impl<T: Debug, S: Scalar, const D: usize> RTree<T, S, D> {
+    fn walk_rec(&self, id: usize, level: usize, f: &mut impl FnMut(&WalkCallbackPayload<T, S, D>)) {
         f(&WalkCallbackPayload {
             id,
             level,
@@ -25,7 +26,11 @@ impl<T: Debug> RTree<T> {
         }
     }
 
-    pub fn walk(&self, f: &mut impl FnMut(&WalkCallbackPayload<T>)) {
+    /// Visits every node in depth-first order. Nothing is visited if the tree is empty.
+    pub fn walk(&self, f: &mut impl FnMut(&WalkCallbackPayload<T, S, D>)) {
+        if self.is_empty() {
+            return;
+        }
         self.walk_rec(0, 0, f);
     }
 }
@@ -0,0 +1,111 @@
+use std::{
+    fmt::{Debug, Display},
+    ops::{Add, Div, Mul, Sub},
+};
+
+/// A coordinate type of [`Point`](crate::Point), [`BoundingBox`](crate::BoundingBox) and [`RTree`](crate::RTree).
+///
+/// It is implemented for `f32`, `f64`, `i32` and `i64`. Coordinates are computed exactly in the scalar type,
+/// while measures used as heuristics, like areas, are computed in `f64` so that integers do not overflow.
+///
+/// ```
+/// use rustree::{BoundingBox, Point, RTree, Scalar};
+///
+/// fn count_around<S: Scalar>(rtree: &RTree<usize, S>, center: Point<S>) -> usize {
+///     rtree
+///         .find_multi(&BoundingBox::from_center_size(center, Point::new(S::ONE, S::ONE)))
+///         .count()
+/// }
+///
+/// let mut rtree = RTree::<usize, i32>::new();
+/// let pt = Point::new(3, 4);
+/// rtree.insert_entry(0, BoundingBox::from_minmax(pt, pt));
+/// assert_eq!(count_around(&rtree, pt), 1);
+/// ```
+pub trait Scalar:
+    Copy
+    + PartialOrd
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Send
+    + Sync
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    /// Equality with a tolerance of machine epsilon for floating point numbers, exact for integers.
+    fn approx_eq(self, other: Self) -> bool;
+    fn to_f64(self) -> f64;
+    /// Converts from `f64`, rounding toward zero for integers.
+    fn from_f64(v: f64) -> Self;
+}
+
+macro_rules! impl_float_scalar {
+    ($t:ty) => {
+        impl Scalar for $t {
+            const ZERO: Self = 0.;
+            const ONE: Self = 1.;
+
+            fn min(self, other: Self) -> Self {
+                <$t>::min(self, other)
+            }
+
+            fn max(self, other: Self) -> Self {
+                <$t>::max(self, other)
+            }
+
+            fn approx_eq(self, other: Self) -> bool {
+                <$t>::abs(self - other) < <$t>::EPSILON
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64(v: f64) -> Self {
+                v as $t
+            }
+        }
+    };
+}
+
+macro_rules! impl_int_scalar {
+    ($t:ty) => {
+        impl Scalar for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn min(self, other: Self) -> Self {
+                Ord::min(self, other)
+            }
+
+            fn max(self, other: Self) -> Self {
+                Ord::max(self, other)
+            }
+
+            fn approx_eq(self, other: Self) -> bool {
+                self == other
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64(v: f64) -> Self {
+                v as $t
+            }
+        }
+    };
+}
+
+impl_float_scalar!(f32);
+impl_float_scalar!(f64);
+impl_int_scalar!(i32);
+impl_int_scalar!(i64);